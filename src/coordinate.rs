@@ -52,6 +52,15 @@ impl Direction {
             Self::None => Self::None,
         }
     }
+
+    /// Turns the direction clockwise by `steps` eighths of a full turn, or counterclockwise if
+    /// `steps` is negative. `None` stays `None`.
+    pub fn turn(self, steps: i32) -> Self {
+        match DIRECTIONS.iter().position(|&d| d == self) {
+            Some(i) => DIRECTIONS[(i as i32 + steps).rem_euclid(8) as usize],
+            None => Self::None,
+        }
+    }
 }
 
 const DIRECTIONS: [Direction; 8] = [
@@ -438,4 +447,12 @@ mod tests {
         u.y = 0;
         assert_eq!(o.direction_to(u), Direction::None);
     }
+
+    #[test]
+    fn turning() {
+        assert_eq!(Direction::North.turn(2), Direction::East);
+        assert_eq!(Direction::North.turn(-1), Direction::NorthWest);
+        assert_eq!(Direction::West.turn(11), Direction::NorthEast);
+        assert_eq!(Direction::None.turn(1), Direction::None);
+    }
 }
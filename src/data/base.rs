@@ -98,7 +98,7 @@ impl DivAssign<i32> for Position {
     }
 }
 
-#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Size {
     pub width: i32,
     pub height: i32,
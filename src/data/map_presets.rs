@@ -0,0 +1,155 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde_derive::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    TomlDeserialization { source: toml::de::Error },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// The built-in presets, the first of which is the default one.
+const BUILT_IN_PRESETS: &str = include_str!("map_presets.toml");
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MapPresets {
+    presets: Vec<MapGenerationPreset>,
+}
+
+impl MapPresets {
+    pub fn load_built_in() -> Result<Self> {
+        toml::from_str(BUILT_IN_PRESETS).context(TomlDeserialization)
+    }
+
+    pub fn all(&self) -> &[MapGenerationPreset] {
+        &self.presets
+    }
+
+    pub fn get(&self, index: usize) -> Option<&MapGenerationPreset> {
+        self.presets.get(index)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.presets
+            .iter()
+            .position(|p| p.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A named list of map generation steps, run in order to generate a map.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MapGenerationPreset {
+    pub name: String,
+    pub description: String,
+    pub steps: Vec<StepDefinition>,
+}
+
+/// A map generation step as written in a preset. Multipliers scale what the step would do in the
+/// Riverlands preset.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum StepDefinition {
+    ClearHeightMap,
+    River {
+        /// How many separate rivers to dig.
+        #[serde(default = "StepDefinition::default_river_count")]
+        count: u32,
+        #[serde(default = "StepDefinition::default_multiplier")]
+        width: f64,
+        #[serde(default = "StepDefinition::default_multiplier")]
+        depth: f64,
+    },
+    /// Smaller rivers that run from the edge of the map into one of the rivers.
+    Tributaries {
+        count: u32,
+        #[serde(default = "StepDefinition::default_tributary_multiplier")]
+        width: f64,
+        #[serde(default = "StepDefinition::default_tributary_multiplier")]
+        depth: f64,
+    },
+    /// Closed basins filled with standing water, away from the rivers.
+    Lakes {
+        #[serde(default = "StepDefinition::default_multiplier")]
+        count: f64,
+        #[serde(default = "StepDefinition::default_multiplier")]
+        radius: f64,
+    },
+    Hills {
+        #[serde(default = "StepDefinition::default_multiplier")]
+        density: f64,
+    },
+    Smoothing,
+    Tiles {
+        /// Land higher than this becomes rock instead of grass.
+        #[serde(default = "StepDefinition::default_rock_height")]
+        rock_height: f32,
+    },
+    Naturify,
+    /// Clusters of minerals inside rock, as listed in the mineral table.
+    Deposits {
+        #[serde(default = "StepDefinition::default_multiplier")]
+        density: f64,
+    },
+    Bogs {
+        #[serde(default = "StepDefinition::default_multiplier")]
+        count: f64,
+        #[serde(default = "StepDefinition::default_multiplier")]
+        radius: f64,
+    },
+    RandomizeWind,
+    CalculateFlow,
+    /// Picks where the camp starts and makes the area around it the player's territory.
+    StartSite,
+    UpdateCache,
+}
+
+impl StepDefinition {
+    fn default_multiplier() -> f64 {
+        1.
+    }
+
+    fn default_river_count() -> u32 {
+        1
+    }
+
+    fn default_tributary_multiplier() -> f64 {
+        0.5
+    }
+
+    fn default_rock_height() -> f32 {
+        4.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_presets_load() {
+        let presets = MapPresets::load_built_in().unwrap();
+        for name in &["Riverlands", "Highlands", "Marsh", "Arid"] {
+            assert!(presets.index_of(name).is_some(), "missing preset {}", name);
+        }
+        assert_eq!(presets.index_of("riverlands"), Some(0));
+    }
+}
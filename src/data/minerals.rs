@@ -0,0 +1,91 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde_derive::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use tcod::Color;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    TomlDeserialization { source: toml::de::Error },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+const BUILT_IN_MINERALS: &str = include_str!("minerals.toml");
+
+/// The table of minerals that deposits can be made of. A mineral is referred to by its index in
+/// the table.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Minerals {
+    minerals: Vec<Mineral>,
+}
+
+impl Minerals {
+    pub fn load_built_in() -> Result<Self> {
+        toml::from_str(BUILT_IN_MINERALS).context(TomlDeserialization)
+    }
+
+    pub fn all(&self) -> &[Mineral] {
+        &self.minerals
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Mineral> {
+        self.minerals.get(index)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.minerals
+            .iter()
+            .position(|m| m.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Mineral {
+    pub name: String,
+    /// How many clusters of this mineral there are on a map of the reference size.
+    pub rarity: f64,
+    /// The smallest and largest number of tiles in a cluster.
+    pub cluster_size: (u32, u32),
+    /// The smallest and largest amount of the mineral held by a single tile.
+    pub amount: (u32, u32),
+    color: (u8, u8, u8),
+}
+
+impl Mineral {
+    pub fn color(&self) -> Color {
+        Color::new(self.color.0, self.color.1, self.color.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_minerals_load() {
+        let minerals = Minerals::load_built_in().unwrap();
+        for mineral in minerals.all() {
+            assert!(mineral.cluster_size.0 <= mineral.cluster_size.1);
+            assert!(mineral.amount.0 <= mineral.amount.1);
+        }
+        assert!(minerals.index_of("iron").is_some());
+    }
+}
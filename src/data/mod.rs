@@ -21,15 +21,23 @@
 pub mod base;
 #[allow(clippy::module_inception)]
 pub mod data;
+pub mod map_presets;
+pub mod minerals;
+pub mod nature_objects;
 pub mod paths;
 pub mod random;
 pub mod settings;
 pub mod tile_sets;
 
+use map_presets::MapPresets;
+use minerals::Minerals;
+use nature_objects::NatureObjectPresets;
 use paths::{PathProvider, Paths};
 use random::DefaultGenerator;
 use settings::Settings;
 
+use crate::game::game_data::StepRegistry;
+
 use rand::rngs::StdRng;
 use slog::{debug, o};
 use snafu::{ResultExt, Snafu};
@@ -38,6 +46,9 @@ use snafu::{ResultExt, Snafu};
 pub enum DataError {
     PathInitialization { source: paths::Error },
     SettingsLoad { source: settings::Error },
+    MapPresetsLoad { source: map_presets::Error },
+    MineralsLoad { source: minerals::Error },
+    NatureObjectsLoad { source: nature_objects::Error },
 }
 
 pub type Result<T = (), E = DataError> = std::result::Result<T, E>;
@@ -46,6 +57,11 @@ pub struct Data {
     pub generator: DefaultGenerator<StdRng>,
     pub paths: Paths,
     pub settings: Settings,
+    pub map_presets: MapPresets,
+    pub minerals: Minerals,
+    pub nature_objects: NatureObjectPresets,
+    /// The map generation steps mods have added.
+    pub map_generation_steps: StepRegistry,
 }
 
 impl Data {
@@ -61,11 +77,18 @@ impl Data {
             Settings::default()
         };
         debug!(method_logger, "{:?}", settings);
+        let map_presets = MapPresets::load_built_in().context(MapPresetsLoad)?;
+        let minerals = Minerals::load_built_in().context(MineralsLoad)?;
+        let nature_objects = NatureObjectPresets::load_built_in().context(NatureObjectsLoad)?;
 
         Ok(Self {
             generator,
             paths,
             settings,
+            map_presets,
+            minerals,
+            nature_objects,
+            map_generation_steps: StepRegistry::new(),
         })
     }
 }
@@ -0,0 +1,124 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::game::game_data::TileType;
+use serde_derive::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use tcod::Color;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    TomlDeserialization { source: toml::de::Error },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+const BUILT_IN_NATURE_OBJECTS: &str = include_str!("nature_objects.toml");
+
+/// The table of nature object presets. A preset is referred to by its index in the table.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NatureObjectPresets {
+    nature_objects: Vec<NatureObjectPreset>,
+}
+
+impl NatureObjectPresets {
+    pub fn load_built_in() -> Result<Self> {
+        toml::from_str(BUILT_IN_NATURE_OBJECTS).context(TomlDeserialization)
+    }
+
+    pub fn all(&self) -> &[NatureObjectPreset] {
+        &self.nature_objects
+    }
+
+    pub fn get(&self, index: usize) -> Option<&NatureObjectPreset> {
+        self.nature_objects.get(index)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.nature_objects
+            .iter()
+            .position(|n| n.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NatureObjectPreset {
+    pub name: String,
+    graphic: u8,
+    color: (u8, u8, u8),
+    /// The chance, out of 50000, of this growing on a tile it has been picked for.
+    pub rarity: i32,
+    pub condition: i32,
+    #[serde(default)]
+    pub tree: bool,
+    #[serde(default)]
+    pub harvestable: bool,
+    #[serde(default)]
+    pub walkable: bool,
+    /// Evil nature objects only grow on corrupted tiles, and other nature objects only grow on
+    /// tiles that are not corrupted.
+    #[serde(default)]
+    pub evil: bool,
+    #[serde(default)]
+    pub ice: bool,
+    #[serde(default = "NatureObjectPreset::default_min_height")]
+    pub min_height: f32,
+    #[serde(default = "NatureObjectPreset::default_max_height")]
+    pub max_height: f32,
+    /// The tile types this grows on.
+    pub tile_types: Vec<TileType>,
+}
+
+impl NatureObjectPreset {
+    fn default_min_height() -> f32 {
+        std::f32::MIN
+    }
+
+    fn default_max_height() -> f32 {
+        std::f32::MAX
+    }
+
+    pub fn graphic(&self) -> char {
+        char::from(self.graphic)
+    }
+
+    pub fn color(&self) -> Color {
+        Color::new(self.color.0, self.color.1, self.color.2)
+    }
+
+    pub fn grows_on(&self, tile_type: TileType, height: f32) -> bool {
+        self.tile_types.contains(&tile_type)
+            && height >= self.min_height
+            && height <= self.max_height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_nature_objects_load() {
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let ice = presets.get(presets.index_of("ice").unwrap()).unwrap();
+        assert!(ice.ice);
+        assert!(presets.all().iter().any(|p| p.tree));
+        assert!(presets.all().iter().any(|p| p.harvestable));
+    }
+}
@@ -268,23 +268,34 @@ impl<S: RngCore + SeedableRng> DefaultGenerator<S> {
         Self {
             rng: S::seed_from_u64(seed),
             seed,
-            //
-            tcod_rng: tcod::random::Rng::new_with_seed(
-                Algo::CMWC,
-                (seed % (u64::from(u32::max_value()) + 1)) as u32,
-            ),
+            tcod_rng: Self::tcod_rng_from_seed(seed),
         }
     }
 
+    /// Creates a tcod [`Rng`] whose seed is derived from `seed`, so that everything that relies
+    /// on tcod's own random number generation stays in lockstep with the generator.
+    ///
+    /// [`Rng`]: /tcod/random/struct.Rng.html
+    fn tcod_rng_from_seed(seed: u64) -> tcod::random::Rng {
+        tcod::random::Rng::new_with_seed(
+            Algo::CMWC,
+            (seed % (u64::from(u32::max_value()) + 1)) as u32,
+        )
+    }
+
     /// Returns the seed used in this generator.
     pub fn seed(&self) -> u64 {
         self.seed
     }
 
-    /// Reseeds the generator.
+    /// Reseeds the generator, including the tcod [`Rng`] returned by [`get_tcod_rng`].
+    ///
+    /// [`Rng`]: /tcod/random/struct.Rng.html
+    /// [`get_tcod_rng`]: trait.Generator.html#tymethod.get_tcod_rng
     pub fn reseed(&mut self, seed: u64) {
         self.rng = S::seed_from_u64(seed);
         self.seed = seed;
+        self.tcod_rng = Self::tcod_rng_from_seed(seed);
     }
 
     /// Reseeds the generator with the default seed.
@@ -470,3 +481,24 @@ impl Generator for StaticGenerator {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::data::random::{DefaultGenerator, Generator};
+
+    #[test]
+    fn reseeding_repeats_sequence() {
+        let mut generator = DefaultGenerator::default_with_seed(1234);
+        let first: Vec<_> = (0..10)
+            .map(|_| generator.generate_integer(0, 1000))
+            .collect();
+
+        generator.reseed(1234);
+        let second: Vec<_> = (0..10)
+            .map(|_| generator.generate_integer(0, 1000))
+            .collect();
+
+        assert_eq!(first, second);
+        assert_eq!(1234, generator.seed());
+    }
+}
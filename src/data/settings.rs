@@ -103,6 +103,8 @@ pub struct Settings {
     pub tutorial: bool,
     pub river_width: u32,
     pub river_depth: u32,
+    #[serde(default = "Settings::default_map_size")]
+    pub map_size: Size,
     pub half_rendering: bool,
     pub compress_saves: bool,
     pub translucent_ui: bool,
@@ -117,6 +119,13 @@ pub struct Settings {
 }
 
 impl Settings {
+    /// The map size used for new games unless the player picks another one.
+    pub const DEFAULT_MAP_SIZE: Size = Size::new(500, 500);
+
+    fn default_map_size() -> Size {
+        Self::DEFAULT_MAP_SIZE
+    }
+
     pub fn load<P: AsRef<Path>>(settings_file_path: P) -> Result<Self> {
         let settings_file_path = settings_file_path.as_ref();
         let settings_string =
@@ -169,6 +178,7 @@ impl Default for Settings {
             tutorial: false,
             river_width: 30,
             river_depth: 5,
+            map_size: Self::DEFAULT_MAP_SIZE,
             half_rendering: false,
             compress_saves: false,
             translucent_ui: false,
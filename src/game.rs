@@ -32,6 +32,7 @@ use tcod::{colors, input, Console};
 
 pub mod game_data;
 pub mod game_state;
+pub mod map_export;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -0,0 +1,235 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::game::game_data::GameData;
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+
+/// The seasons of the year, one for each month.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Season {
+    EarlySpring,
+    Spring,
+    LateSpring,
+    EarlySummer,
+    Summer,
+    LateSummer,
+    EarlyFall,
+    Fall,
+    LateFall,
+    EarlyWinter,
+    Winter,
+    LateWinter,
+}
+
+const SEASONS: [Season; 12] = [
+    Season::EarlySpring,
+    Season::Spring,
+    Season::LateSpring,
+    Season::EarlySummer,
+    Season::Summer,
+    Season::LateSummer,
+    Season::EarlyFall,
+    Season::Fall,
+    Season::LateFall,
+    Season::EarlyWinter,
+    Season::Winter,
+    Season::LateWinter,
+];
+
+impl Season {
+    /// The season of the given month of the year, counting from 0.
+    pub fn of_month(month: i32) -> Self {
+        SEASONS[month.rem_euclid(SEASONS.len() as i32) as usize]
+    }
+
+    pub fn next(self) -> Self {
+        Self::of_month(self as i32 + 1)
+    }
+
+    /// Whether this is the middle of a season, rather than its early or late part.
+    pub fn is_middle(self) -> bool {
+        match self {
+            Self::Spring | Self::Summer | Self::Fall | Self::Winter => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_winter(self) -> bool {
+        match self {
+            Self::EarlyWinter | Self::Winter | Self::LateWinter => true,
+            _ => false,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::EarlySpring => "Early Spring",
+            Self::Spring => "Spring",
+            Self::LateSpring => "Late Spring",
+            Self::EarlySummer => "Early Summer",
+            Self::Summer => "Summer",
+            Self::LateSummer => "Late Summer",
+            Self::EarlyFall => "Early Fall",
+            Self::Fall => "Fall",
+            Self::LateFall => "Late Fall",
+            Self::EarlyWinter => "Early Winter",
+            Self::Winter => "Winter",
+            Self::LateWinter => "Late Winter",
+        }
+    }
+}
+
+impl Default for Season {
+    fn default() -> Self {
+        Self::EarlySpring
+    }
+}
+
+impl Display for Season {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// What changed when the calendar moved on by a tick.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CalendarEvent {
+    NewDay,
+    /// A new month began, bringing the given season. A new year begins with early spring.
+    NewSeason(Season),
+}
+
+/// Something that happens whenever a new season begins, such as crops ripening or a raid being
+/// planned. Mods add their own with [`GameData::add_season_listener`].
+pub trait SeasonListener {
+    /// The name the listener can be removed by.
+    fn name(&self) -> Cow<'_, str>;
+
+    /// Called when `season` begins, including the first season of a new game.
+    fn season_changed(&self, game_data: &mut GameData, season: Season);
+}
+
+/// Keeps track of the time that has passed in the game, from ticks up to years.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Calendar {
+    /// How many ticks into the current day it is.
+    tick: i32,
+    /// The day of the month, counting from 0.
+    day: i32,
+    /// The month of the year, counting from 0, which is also the season.
+    month: i32,
+    /// The year, counting from 0.
+    year: i32,
+    /// How many ticks have passed since the game began.
+    age: u64,
+}
+
+impl Calendar {
+    pub const TICKS_PER_DAY: i32 = GameData::UPDATES_PER_SECOND * 4;
+    pub const DAYS_PER_MONTH: i32 = 30;
+    pub const MONTHS_PER_YEAR: i32 = 12;
+
+    /// Creates a calendar at the very start of early spring in the first year.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn season(&self) -> Season {
+        Season::of_month(self.month)
+    }
+
+    /// The day of the month, counting from 1.
+    pub fn day(&self) -> i32 {
+        self.day + 1
+    }
+
+    /// The year, counting from 1.
+    pub fn year(&self) -> i32 {
+        self.year + 1
+    }
+
+    /// How many ticks have passed since the game began.
+    pub fn age(&self) -> u64 {
+        self.age
+    }
+
+    /// Moves the calendar on by one tick, returning what changed, if a new day began.
+    pub fn advance(&mut self) -> Option<CalendarEvent> {
+        self.age += 1;
+        self.tick += 1;
+        if self.tick < Self::TICKS_PER_DAY {
+            return None;
+        }
+
+        self.tick = 0;
+        self.day += 1;
+        if self.day < Self::DAYS_PER_MONTH {
+            return Some(CalendarEvent::NewDay);
+        }
+
+        self.day = 0;
+        self.month += 1;
+        if self.month == Self::MONTHS_PER_YEAR {
+            self.month = 0;
+            self.year += 1;
+        }
+
+        Some(CalendarEvent::NewSeason(self.season()))
+    }
+}
+
+impl Display for Calendar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatResult {
+        write!(
+            f,
+            "Day {} of {}, year {}",
+            self.day(),
+            self.season(),
+            self.year()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seasons_follow_the_months_and_wrap_around_into_a_new_year() {
+        let mut calendar = Calendar::new();
+        let mut events = vec![];
+        let month = Calendar::TICKS_PER_DAY * Calendar::DAYS_PER_MONTH;
+        for _ in 0..month * Calendar::MONTHS_PER_YEAR {
+            if let Some(CalendarEvent::NewSeason(season)) = calendar.advance() {
+                events.push(season);
+            }
+        }
+
+        assert_eq!(events.len(), 12);
+        assert_eq!(events[0], Season::Spring);
+        assert_eq!(events[11], Season::EarlySpring);
+        assert_eq!(calendar.year(), 2);
+        assert_eq!(calendar.day(), 1);
+        assert_eq!(Season::LateWinter.next(), Season::EarlySpring);
+        assert_eq!(calendar.to_string(), "Day 1 of Early Spring, year 2");
+    }
+}
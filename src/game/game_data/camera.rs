@@ -19,6 +19,7 @@
 */
 
 use crate::coordinate::Coordinate;
+use crate::data::base::Size;
 use crate::game::game_data::map::Map;
 use crate::game::GameRef;
 use tcod::input::KeyCode;
@@ -32,9 +33,12 @@ impl Camera {
     const SMALL_MOVEMENT: i32 = 1;
     const LARGE_MOVEMENT: i32 = 10;
 
-    pub fn new() -> Self {
-        // TODO: When map sizes are no longer hard coded, find a reasonable default for this
-        Self { x: 180., y: 180. }
+    /// Creates a camera looking at the center of a map of the given `extent`.
+    pub fn new(extent: Size) -> Self {
+        let mut camera = Self { x: 0., y: 0. };
+        camera.center_on(Coordinate::from(extent) / 2);
+
+        camera
     }
 
     pub fn x(&self) -> f64 {
@@ -22,6 +22,7 @@ use crate::data::base::Position;
 use crate::game::game_data::map::MapDrawable;
 use crate::util::SafeConsole;
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Tag {
     Stockpile,
     FarmPlot,
@@ -22,7 +22,7 @@ use crate::coordinate::Coordinate;
 
 pub struct Entity {
     pos: Coordinate,
-    uid: i32,
+    uid: isize,
     zone: i32,
     reserved: bool,
     name: String,
@@ -35,3 +35,37 @@ pub struct Entity {
     strobe: f32,
     // static int uids;
 }
+
+impl Entity {
+    pub fn new(pos: Coordinate, uid: isize, name: String) -> Self {
+        Self {
+            pos,
+            uid,
+            zone: 0,
+            reserved: false,
+            name,
+            faction: -1,
+            velocity: 0,
+            next_velocity_move: 0,
+            velocity_target: pos,
+            bulk: 0,
+            strobe: 0.,
+        }
+    }
+
+    pub fn pos(&self) -> Coordinate {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: Coordinate) {
+        self.pos = pos;
+    }
+
+    pub fn uid(&self) -> isize {
+        self.uid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
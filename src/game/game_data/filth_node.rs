@@ -19,6 +19,7 @@
 */
 
 use crate::coordinate::Coordinate;
+use crate::data::random::Generator;
 use crate::game::game_data::map::MapGraphicDrawable;
 use tcod::Color;
 
@@ -31,9 +32,37 @@ pub struct FilthNode {
 }
 
 impl FilthNode {
+    /// The most filth a single tile holds; any more spills over onto the next tile downhill.
+    pub const MAX_DEPTH: i32 = 5;
+
+    pub fn new(pos: Coordinate, depth: i32, generator: &mut dyn Generator) -> Self {
+        let mut filth = Self {
+            pos,
+            depth: 0,
+            graphic: '~',
+            color: Color::new(
+                generator.generate_integer(130, 170) as u8,
+                generator.generate_integer(80, 120) as u8,
+                generator.generate_integer(0, 30) as u8,
+            ),
+        };
+        filth.set_depth(depth);
+
+        filth
+    }
+
+    pub fn position(&self) -> Coordinate {
+        self.pos
+    }
+
     pub fn depth(&self) -> i32 {
         self.depth
     }
+
+    pub fn set_depth(&mut self, depth: i32) {
+        self.depth = depth;
+        self.graphic = if depth < Self::MAX_DEPTH { '~' } else { '#' };
+    }
 }
 
 impl MapGraphicDrawable for FilthNode {
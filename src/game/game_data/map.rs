@@ -19,50 +19,67 @@
 */
 use tcod::heightmap::HeightMap;
 
+mod corruption;
+mod deposit;
+mod dirty_chunks;
+mod export;
+mod filth;
 mod fire;
+mod fov;
+mod ice;
 mod item;
 mod marker;
 mod nature;
 mod npc;
+mod path;
 mod spell;
+mod start_site;
+mod territory;
 mod tile;
+mod water;
 mod weather;
+mod zones;
 
+pub use corruption::*;
+pub use deposit::*;
+pub use dirty_chunks::*;
+pub use export::*;
 pub use fire::*;
+pub use fov::*;
 pub use item::*;
 pub use marker::*;
 pub use nature::*;
 pub use npc::*;
+pub use path::*;
 pub use spell::*;
+pub use territory::*;
 pub use tile::*;
 pub use weather::*;
+pub use zones::*;
 
 use crate::coordinate::{Coordinate, Direction};
 use crate::data::base::{Position, Rectangle, Size};
+use crate::data::nature_objects::NatureObjectPresets;
 use crate::data::random::Generator;
 use crate::game::game_data::camera::Camera;
 use crate::game::game_data::construction::Construction;
 use crate::game::game_data::filth_node::FilthNode;
 use crate::game::game_data::map::nature::NatureObject;
+use crate::game::game_data::map_generation::RiverCurve;
 use crate::game::game_data::water_node::WaterNode;
 use crate::util::extras::Array2DCoordinateAccessor;
 use crate::util::tcod::Chars;
-use crate::util::{compare_and_pick, dual_map, Array2D, SafeConsole};
+use crate::util::{compare_and_pick, dual_map, Array2D, SafeConsole, SlotHandle, SlotList};
 use itertools::iproduct;
 use shrinkwraprs::Shrinkwrap;
-use std::cell::{Ref, RefCell, RefMut};
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap, HashSet};
-use std::rc::Rc;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
 use tcod::console::Offscreen;
 use tcod::{colors, BackgroundFlag, Color};
 
-const HARDCODED_WIDTH: usize = 500;
-const HARDCODED_HEIGHT: usize = 500;
-
 pub struct Map {
     pub height_map: HeightMap,
-    tile_map: Array2D<Tile>,
+    tile_map: TileMap,
     cached_tile_map: Array2D<CacheTile>,
     pub extent: Size,
     pub water_level: f32,
@@ -70,53 +87,85 @@ pub struct Map {
     // TODO: Use enum/bitflags?
     map_markers: Vec<(i32, MapMarker)>,
     marker_ids: i32,
-    changed_tiles: HashSet<Coordinate>,
+    entity_uids: isize,
+    dirty_chunks: DirtyChunks,
+    /// The chunks in view as last drawn, by chunk index. Chunks that changed since are redrawn
+    /// each frame, and chunks that go out of view are dropped.
+    terrain_chunks: HashMap<usize, Offscreen>,
     weather: Weather,
 
     // Moved from Game
-    water_list: Vec<Rc<RefCell<WaterNode>>>,
-    filth_list: Vec<FilthNode>,
+    water_list: SlotList<WaterNode>,
+    filth_list: SlotList<FilthNode>,
     static_construction_list: EntityList<Construction>,
     dynamic_construction_list: EntityList<Construction>,
     nature_list: EntityList<NatureObject>,
     item_list: EntityList<Item>,
     npc_list: EntityList<Npc>,
-    fire_list: Vec<FireNode>,
+    fire_list: SlotList<FireNode>,
+    smoke_list: Vec<SmokeNode>,
+    corruption_sources: SlotList<CorruptionSource>,
+    corruption_effects: Vec<Box<dyn CorruptionEffect>>,
+    path_finder: PathFinder,
+    zones: Zones,
+    visibility: HashMap<i32, FactionVisibility>,
     spell_list: Vec<Spell>,
 }
 
 impl Map {
-    pub fn new() -> Self {
+    /// Out of every this many tiles, one is naturified each tick.
+    const TILES_PER_REGROWTH: i32 = 1000;
+
+    pub fn new(extent: Size) -> Self {
+        let width = extent.width as usize;
+        let height = extent.height as usize;
         Self {
-            height_map: HeightMap::new(HARDCODED_WIDTH as i32, HARDCODED_HEIGHT as i32),
-            tile_map: Array2D::new(HARDCODED_WIDTH, HARDCODED_HEIGHT),
-            cached_tile_map: Array2D::new_with(HARDCODED_WIDTH, HARDCODED_HEIGHT, |i, e| {
+            height_map: HeightMap::new(extent.width, extent.height),
+            tile_map: TileMap::new(extent),
+            cached_tile_map: Array2D::new_with(width, height, |i, e| {
                 let mut cache_tile = CacheTile::default();
                 cache_tile.x = i as i32;
                 cache_tile.y = e as i32;
 
                 cache_tile
             }),
-            extent: Size::new(HARDCODED_WIDTH as i32, HARDCODED_HEIGHT as i32),
+            extent,
             water_level: -0.8,
             overlays: vec![],
             map_markers: vec![],
             marker_ids: 0,
-            changed_tiles: HashSet::new(),
+            entity_uids: 0,
+            dirty_chunks: DirtyChunks::new(extent),
+            terrain_chunks: HashMap::new(),
             weather: Weather::new(),
 
-            water_list: vec![],
-            filth_list: vec![],
+            water_list: SlotList::new(),
+            filth_list: SlotList::new(),
             static_construction_list: EntityList::new(),
             dynamic_construction_list: EntityList::new(),
             nature_list: EntityList::new(),
             item_list: EntityList::new(),
             npc_list: EntityList::new(),
-            fire_list: vec![],
+            fire_list: SlotList::new(),
+            smoke_list: vec![],
+            corruption_sources: SlotList::new(),
+            corruption_effects: vec![],
+            path_finder: PathFinder::new(),
+            zones: Zones::new(extent),
+            visibility: HashMap::new(),
             spell_list: vec![],
         }
     }
 
+    // GetType in original
+    pub fn tile_type(&self, p: Coordinate) -> TileType {
+        if self.extent.is_inside(p) {
+            self.tile_map.tile_type(p)
+        } else {
+            TileType::None
+        }
+    }
+
     // ResetType in original
     pub fn set_tile_type(
         &mut self,
@@ -136,107 +185,401 @@ impl Map {
         generator: &mut dyn Generator,
     ) {
         if self.extent.is_inside(p) {
-            self.tile_map.by_coordinate_mut(p).reset_type_and_height(
-                tile_type,
-                tile_height,
-                generator,
+            self.tile_map
+                .reset_type_and_height(p, tile_type, tile_height, generator);
+            self.dirty_chunks.mark(p);
+        }
+    }
+
+    /// Wears down the tile at `p` as a creature walks over it, so that well-trodden ground turns
+    /// into a trail, and in the end into mud.
+    pub fn walk_over(&mut self, p: Coordinate, generator: &mut dyn Generator) {
+        if self.extent.is_inside(p) {
+            let height = self.height_map.get_value(p.x, p.y);
+            self.tile_map.walk_over(p, height, generator);
+            self.dirty_chunks.mark(p);
+        }
+    }
+
+    /// Naturifies a few tiles picked at random each tick, so that over time trails nobody uses
+    /// any more fade, burnt ground recovers and plants grow back where they were cleared.
+    pub fn update_regrowth(
+        &mut self,
+        presets: &NatureObjectPresets,
+        generator: &mut dyn Generator,
+    ) {
+        let tiles = (self.extent.area() / Self::TILES_PER_REGROWTH).max(1);
+        for _ in 0..tiles {
+            let p = generator.generate_coordinate_within_rectangle(
+                Coordinate::ORIGIN,
+                Coordinate::from(self.extent) - 1,
             );
-            self.changed_tiles.insert(p);
+            self.naturify(p, presets, generator);
         }
     }
 
     // TODO: Rename to something better. Reduces effects like walking, burning and corrupting.
-    pub fn naturify(&mut self, p: Coordinate) {
+    pub fn naturify(
+        &mut self,
+        p: Coordinate,
+        presets: &NatureObjectPresets,
+        generator: &mut dyn Generator,
+    ) {
         if self.extent.is_inside(p) {
-            let mut tile = self.tile_map.by_coordinate_mut(p);
-            if tile.walked_over > 0 {
-                tile.walked_over -= 1;
+            if self.tile_map.walked_over(p) > 0 {
+                let height = self.height_map.get_value(p.x, p.y);
+                self.tile_map.recover_from_walking(p, height, generator);
+                self.dirty_chunks.mark(p);
             }
-            if tile.burnt > 0 {
-                tile.burn(-1);
+            if self.tile_map.burnt(p) > 0 {
+                self.tile_map.burn(p, -1);
+                self.dirty_chunks.mark(p);
             }
-            if tile.walked_over == 0 && tile.nature_object_ref < 0 && tile.construction < 0 {
-                // TODO: Extract into own method?
-                let mut nature_objects = 0;
-                let begin = self.extent.shrink(p - 2);
-                let end = self.extent.shrink(p + 2);
-                for ix in begin.x..=end.x {
-                    for iy in begin.y..=end.y {
-                        if self.tile_map[ix as usize][iy as usize].nature_object_ref >= 0 {
-                            nature_objects += 1;
-                        }
-                    }
-                }
-                tile = self.tile_map.by_coordinate_mut(p);
-
+            // Growing a nature object marks its chunk by itself
+            if self.tile_map.walked_over(p) == 0
+                && self.tile_map.nature_object(p) < 0
+                && self.tile_map.construction(p) < 0
+            {
+                let nature_objects = self
+                    .tile_map
+                    .count_nature_objects(self.extent.shrink(p - 2), self.extent.shrink(p + 2));
                 //Corrupted areas have less flora
-                let nature_objects_target = if tile.corruption < 100 { 6 } else { 1 };
+                let nature_objects_target = if self.tile_map.is_corrupted(p) { 1 } else { 6 };
                 if nature_objects < nature_objects_target {
-                    // TODO: Create nature object
-                    // Game::Inst()->CreateNatureObject(p, natureObjects);
-                    unimplemented!()
+                    self.grow_nature_object(p, nature_objects, presets, generator);
                 }
             }
         }
     }
 
+    /// Maybe grows a nature object at `p`, picking among the presets that grow there weighted by
+    /// their rarity.
+    ///
+    /// CreateNatureObject in original
+    fn grow_nature_object(
+        &mut self,
+        p: Coordinate,
+        surrounding_nature_objects: i32,
+        presets: &NatureObjectPresets,
+        generator: &mut dyn Generator,
+    ) {
+        if !self.tile_map.is_walkable(p) || self.tile_map.has_water(p) || self.tile_map.has_filth(p)
+        {
+            return;
+        }
+
+        let height = self.height_map.get_value(p.x, p.y);
+        let tile_type = self.tile_map.tile_type(p);
+        let evil = self.tile_map.is_corrupted(p);
+        // Trees only grow where there aren't already many nature objects around
+        let allow_trees = surrounding_nature_objects < 4;
+
+        let mut chosen: Option<(i32, usize)> = None;
+        for (i, preset) in presets.all().iter().enumerate() {
+            if !preset.ice
+                && preset.evil == evil
+                && (allow_trees || !preset.tree)
+                && preset.grows_on(tile_type, height)
+            {
+                let score = generator.generate_integer_up_to((preset.rarity - 1).max(0))
+                    + generator.generate_integer_up_to(2);
+                if chosen.map_or(true, |c| (score, i) > c) {
+                    chosen = Some((score, i));
+                }
+            }
+        }
+        let chosen = match chosen {
+            Some((_, chosen)) => chosen,
+            None => return,
+        };
+
+        // Plants are a little more common close to the water
+        let mut rarity = presets.all()[chosen].rarity;
+        if (height - self.water_level).abs() < 0.5 {
+            rarity -= rarity / 5;
+        }
+        if (height - self.water_level).abs() < 1.0 {
+            rarity -= rarity / 5;
+        }
+
+        if generator.generate_integer_up_to(50000) < rarity {
+            self.create_nature_object(p, chosen, presets);
+        }
+    }
+
+    /// Places a nature object made from the given preset at `p`, returning its uid, or `None` if
+    /// there is already a nature object there.
+    pub fn create_nature_object(
+        &mut self,
+        p: Coordinate,
+        preset_index: usize,
+        presets: &NatureObjectPresets,
+    ) -> Option<isize> {
+        let preset = presets.get(preset_index)?;
+        if !self.extent.is_inside(p)
+            || self.tile_map.nature_object(p) >= 0
+            || self.tile_map.construction(p) >= 0
+        {
+            return None;
+        }
+
+        let uid = self.entity_uids;
+        self.entity_uids += 1;
+        self.nature_list
+            .insert(uid, NatureObject::new(uid, p, preset_index, preset));
+
+        self.tile_map.set_nature_object(p, uid);
+        self.tile_map.set_walkable(p, preset.walkable);
+        self.tile_map.set_blocks_water(p, !preset.walkable);
+        self.tile_map.set_blocks_light(p, preset.tree);
+        self.tile_map.set_buildable(p, false);
+        self.dirty_chunks.mark(p);
+
+        Some(uid)
+    }
+
+    /// Removes the nature object with the given uid, freeing up the tile it stood on.
+    pub fn remove_nature_object(&mut self, uid: isize) -> Option<NatureObject> {
+        let nature_object = self.nature_list.remove(uid)?;
+        let p = nature_object.pos();
+
+        self.tile_map.set_nature_object(p, -1);
+        self.tile_map.reset_flags(p);
+        self.tile_map.set_blocks_water(p, false);
+        self.tile_map.set_blocks_light(p, false);
+        self.dirty_chunks.mark(p);
+
+        Some(nature_object)
+    }
+
+    /// Places a creature at `p`, returning its uid, or `None` if `p` is outside the map.
+    pub fn create_npc(
+        &mut self,
+        p: Coordinate,
+        name: &str,
+        graphic: char,
+        color: Color,
+    ) -> Option<isize> {
+        if !self.extent.is_inside(p) {
+            return None;
+        }
+
+        let uid = self.entity_uids;
+        self.entity_uids += 1;
+        self.npc_list
+            .insert(uid, Npc::new(uid, p, name, graphic, color));
+
+        Some(uid)
+    }
+
+    /// Moves the creature `uid` onto `p`, wearing down the ground it steps on. Returns whether it
+    /// moved, which it can't onto a tile nobody can walk on.
+    pub fn move_npc(&mut self, uid: isize, p: Coordinate, generator: &mut dyn Generator) -> bool {
+        if !self.extent.is_inside(p) || !self.tile_map.is_walkable(p) {
+            return false;
+        }
+
+        match self.npc_list.get_mut(uid) {
+            Some(npc) => npc.set_pos(p),
+            None => return false,
+        }
+        self.walk_over(p, generator);
+
+        true
+    }
+
+    /// Places an item at `p`, returning its uid, or `None` if `p` is outside the map.
+    pub fn create_item(
+        &mut self,
+        p: Coordinate,
+        name: &str,
+        graphic: char,
+        color: Color,
+    ) -> Option<isize> {
+        if !self.extent.is_inside(p) {
+            return None;
+        }
+
+        let uid = self.entity_uids;
+        self.entity_uids += 1;
+        self.item_list
+            .insert(uid, Item::new(uid, p, name, graphic, color));
+
+        Some(uid)
+    }
+
+    pub fn nature_object(&self, p: Coordinate) -> Option<&NatureObject> {
+        if !self.extent.is_inside(p) {
+            return None;
+        }
+        self.nature_list.get(&self.tile_map.nature_object(p))
+    }
+
+    pub fn set_overlay(&mut self, overlay: Overlay, shown: bool) {
+        if self.overlays.contains(&overlay) == shown {
+            return;
+        }
+        if shown {
+            self.overlays.push(overlay);
+        } else {
+            self.overlays.retain(|&o| o != overlay);
+        }
+        self.dirty_chunks.mark_all_for_render();
+    }
+
     pub fn randomize_wind(&mut self, generator: &mut dyn Generator) {
         self.weather.randomize_wind(generator);
     }
 
-    pub fn calculate_flow(&mut self, px: [i32; 4], py: [i32; 4], generator: &mut dyn Generator) {
-        self.set_river_flow(px, py, generator);
+    /// Sets the flow of every tile. Rivers are handled first, each only over its own stretch of
+    /// water, so that where a tributary joins a river it flows into it instead of overriding it.
+    pub fn calculate_flow(&mut self, rivers: &[RiverCurve], generator: &mut dyn Generator) {
+        let mut claimed = HashSet::new();
+        let (main_rivers, tributaries): (Vec<_>, Vec<_>) =
+            rivers.iter().partition(|r| r.joins.is_none());
+
+        for river in main_rivers {
+            let corridor = self.river_corridor(river, &claimed);
+            self.set_river_flow(river.px, river.py, &corridor, generator);
+            claimed.extend(corridor);
+        }
+        for tributary in tributaries {
+            let corridor = self.river_corridor(tributary, &claimed);
+            self.set_tributary_flow(&corridor, &claimed, generator);
+            claimed.extend(corridor);
+        }
+
         self.set_ground_flow(generator);
     }
 
     pub fn add_to_cache(&mut self, p: Coordinate) {
-        self.changed_tiles.insert(p);
+        self.dirty_chunks.mark(p);
+    }
+
+    /// Marks every tile in the rectangle from `low` to `high`, both inclusive, as changed, such as
+    /// after a flood or a fire has swept over it.
+    pub fn add_rectangle_to_cache(&mut self, low: Coordinate, high: Coordinate) {
+        self.dirty_chunks.mark_rectangle(low, high);
+    }
+
+    /// Marks the whole map as changed, such as when the season changes.
+    pub fn add_all_to_cache(&mut self) {
+        self.dirty_chunks.mark_all();
     }
 
     pub fn update_cache(&mut self) {
-        for tile_coord in self.changed_tiles.drain() {
-            let construction = self.tile_map.by_coordinate(tile_coord).construction;
-            self.cached_tile_map
-                .by_coordinate_mut(tile_coord)
-                .update_from(
-                    self.tile_map.by_coordinate(tile_coord),
-                    [
-                        &self.static_construction_list,
-                        &self.dynamic_construction_list,
-                    ]
-                    .construction(construction),
-                );
+        let mut walkability_changed = vec![];
+        for tile_coord in self.dirty_chunks.drain_changed_tiles() {
+            let construction = self.tile_map.construction(tile_coord);
+            let water_list = &self.water_list;
+            let water = self
+                .tile_map
+                .water(tile_coord)
+                .and_then(|w| water_list.get(w));
+            let cache_tile = self.cached_tile_map.by_coordinate_mut(tile_coord);
+            let was_walkable = cache_tile.is_walkable();
+            cache_tile.update_from(
+                &self.tile_map,
+                tile_coord,
+                [
+                    &self.static_construction_list,
+                    &self.dynamic_construction_list,
+                ]
+                .construction(construction),
+                water,
+            );
+            if cache_tile.is_walkable() != was_walkable {
+                walkability_changed.push(tile_coord);
+            }
+        }
+
+        if !walkability_changed.is_empty() {
+            self.zones
+                .update(&self.cached_tile_map, &walkability_changed);
         }
     }
 
+    /// Whether `b` can be walked to from `a`, going by the tile cache.
+    pub fn reachable(&self, a: Coordinate, b: Coordinate) -> bool {
+        self.zones.reachable(a, b)
+    }
+
+    /// What the tile at `p` looked like the last time the cache was updated.
+    pub fn cache_tile(&self, p: Coordinate) -> Option<&CacheTile> {
+        if !self.extent.is_inside(p) {
+            return None;
+        }
+        Some(self.cached_tile_map.by_coordinate(p))
+    }
+
     pub fn filth(&self, p: Coordinate) -> Option<&FilthNode> {
-        self.tile_map.by_coordinate(p).filth.as_ref()
+        self.tile_map.filth(p).and_then(|f| self.filth_list.get(f))
     }
 
-    pub fn remove_filth(&mut self, p: Coordinate) {
-        unimplemented!()
+    pub fn remove_filth(&mut self, p: Coordinate) -> Option<FilthNode> {
+        let filth = self.tile_map.filth(p)?;
+        self.tile_map.set_filth(p, None);
+        self.dirty_chunks.mark(p);
+        self.filth_list.remove(filth)
     }
 
-    pub fn water(&self, p: Coordinate) -> Option<Ref<WaterNode>> {
-        self.tile_map
-            .by_coordinate(p)
-            .water
-            .as_ref()
-            .map(|w| w.borrow())
+    /// Every filth node on the map, along with its handle.
+    pub fn filth_nodes(&self) -> impl Iterator<Item = (SlotHandle<FilthNode>, &FilthNode)> {
+        self.filth_list.iter()
     }
 
-    pub fn water_mut(&mut self, p: Coordinate) -> Option<RefMut<WaterNode>> {
-        self.tile_map
-            .by_coordinate_mut(p)
-            .water
-            .as_mut()
-            .map(|w| w.borrow_mut())
+    pub fn water(&self, p: Coordinate) -> Option<&WaterNode> {
+        self.tile_map.water(p).and_then(|w| self.water_list.get(w))
     }
 
-    pub fn add_water(&mut self, p: Coordinate, water: WaterNode) {
-        let water_rc = Rc::new(RefCell::new(water));
-        self.water_list.push(Rc::clone(&water_rc));
-        self.tile_map.by_coordinate_mut(p).water = Some(water_rc);
+    /// Lets `update` change the water at `p`, if there is any, and returns what it returns. The
+    /// tile is only redrawn if the water actually changed.
+    pub fn modify_water<F, R>(&mut self, p: Coordinate, update: F) -> Option<R>
+    where
+        F: FnOnce(&mut WaterNode) -> R,
+    {
+        let (result, changed) = {
+            let water = self.tile_map.water(p)?;
+            let water = self.water_list.get_mut(water)?;
+            let before = *water;
+            let result = update(water);
+            (result, *water != before)
+        };
+        if changed {
+            self.dirty_chunks.mark(p);
+        }
+
+        Some(result)
+    }
+
+    /// Puts `water` on the tile at `p`, replacing any water already there. Any filth on the tile
+    /// is mixed into the water.
+    pub fn add_water(&mut self, p: Coordinate, mut water: WaterNode) -> SlotHandle<WaterNode> {
+        self.remove_water(p);
+        if let Some(filth) = self.remove_filth(p) {
+            water.add_filth(filth.depth());
+        }
+        let handle = self.water_list.insert(water);
+        self.tile_map.set_water(p, Some(handle));
+        self.dirty_chunks.mark(p);
+
+        handle
+    }
+
+    pub fn remove_water(&mut self, p: Coordinate) -> Option<WaterNode> {
+        let water = self.tile_map.water(p)?;
+        self.tile_map.set_water(p, None);
+        self.dirty_chunks.mark(p);
+        self.water_list.remove(water)
+    }
+
+    /// Every water node on the map, along with its handle.
+    pub fn water_nodes(&self) -> impl Iterator<Item = (SlotHandle<WaterNode>, &WaterNode)> {
+        self.water_list.iter()
+    }
+
+    pub fn water_node(&self, water: SlotHandle<WaterNode>) -> Option<&WaterNode> {
+        self.water_list.get(water)
     }
 
     pub fn render_map(&mut self, mut render_data: MapRenderData) {
@@ -253,31 +596,48 @@ impl Map {
         );
 
         let mut viewport = self.render_viewport(&render_data, up_left);
+        let viewport_size = Coordinate::new(viewport.width(), viewport.height());
 
         if self.overlays.contains(&Overlay::Terrain) {
             self.static_construction_list.draw(&mut viewport, up_left);
             self.dynamic_construction_list.draw(&mut viewport, up_left);
 
             for item in self.item_list.values() {
-                item.draw(&mut viewport, up_left);
+                let p = item.pos() - up_left;
+                if p.inside_extent(Coordinate::ORIGIN, viewport_size) {
+                    item.draw(&mut viewport, p);
+                }
             }
         }
 
-        for (_, marker) in &self.map_markers {
-            if (Position::from(up_left) + render_data.viewport.size)
-                .contains_position(marker.pos.into())
-            {
-                marker.draw(render_data.console, marker.pos - up_left);
+        for npc in self.npc_list.values() {
+            let p = npc.pos() - up_left;
+            if p.inside_extent(Coordinate::ORIGIN, viewport_size) {
+                npc.draw(&mut viewport, p);
+            }
+        }
+        // Fires flicker and smoke drifts every tick, so they are drawn on top of the terrain
+        for (_, fire) in self.fire_list.iter() {
+            let p = fire.position() - up_left;
+            if p.inside_extent(Coordinate::ORIGIN, viewport_size) {
+                fire.draw(&mut viewport, p);
+            }
+        }
+        for smoke in &self.smoke_list {
+            let p = smoke.position() - up_left;
+            if p.inside_extent(Coordinate::ORIGIN, viewport_size) {
+                smoke.draw(&mut viewport, p);
             }
         }
-
-        self.npc_list.draw(&mut viewport, up_left);
-        self.fire_list
-            .iter()
-            .for_each(|f| f.draw(&mut viewport, up_left));
         self.spell_list
             .iter()
             .for_each(|s| s.draw(&mut viewport, up_left));
+        for (_, marker) in &self.map_markers {
+            let p = marker.pos - up_left;
+            if p.inside_extent(Coordinate::ORIGIN, viewport_size) {
+                marker.draw(&mut viewport, p);
+            }
+        }
 
         render_data.console.blit::<tcod::console::Root, _>(
             &viewport,
@@ -288,8 +648,45 @@ impl Map {
         );
     }
 
+    /// Finds the water tiles that were dug out by `river`, leaving out those already `claimed` by
+    /// another river.
+    fn river_corridor(
+        &self,
+        river: &RiverCurve,
+        claimed: &HashSet<Coordinate>,
+    ) -> BTreeSet<Coordinate> {
+        let reach = river.radius.ceil() as i32 + 2;
+        let samples = (river.length() / f64::from((reach / 4).max(1)))
+            .ceil()
+            .max(1.) as i32;
+
+        let mut corridor = BTreeSet::new();
+        for sample in 0..=samples {
+            let (x, y) = river.point_at(f64::from(sample) / f64::from(samples));
+            let center = Coordinate::new(x as i32, y as i32);
+            for (dy, dx) in iproduct!(-reach..=reach, -reach..=reach) {
+                let pos = center + Coordinate::new(dx, dy);
+                if dx * dx + dy * dy <= reach * reach
+                    && self.extent.is_inside(pos)
+                    && !claimed.contains(&pos)
+                    && self.tile_map.has_water(pos)
+                {
+                    corridor.insert(pos);
+                }
+            }
+        }
+
+        corridor
+    }
+
     #[allow(clippy::nonminimal_bool)]
-    fn set_river_flow(&mut self, px: [i32; 4], py: [i32; 4], generator: &mut dyn Generator) {
+    fn set_river_flow(
+        &mut self,
+        px: [i32; 4],
+        py: [i32; 4],
+        corridor: &BTreeSet<Coordinate>,
+        generator: &mut dyn Generator,
+    ) {
         #[derive(Copy, Clone, Eq, PartialEq)]
         struct Unfinished(i32, Coordinate);
         impl Ord for Unfinished {
@@ -348,18 +745,15 @@ impl Map {
                 }
             }
             if result[0] > result[1] {
-                self.tile_map.by_coordinate_mut(current).flow = x_directions[stage];
+                self.tile_map.set_flow(current, x_directions[stage]);
             } else {
-                self.tile_map.by_coordinate_mut(current).flow = y_directions[stage];
+                self.tile_map.set_flow(current, y_directions[stage]);
             }
 
             for y in current.y - 1..=current.y + 1 {
                 for x in current.x - 1..=current.x + 1 {
                     let pos = Coordinate::new(x, y);
-                    if self.extent.is_inside(pos)
-                        && !touched.contains(&pos)
-                        && self.tile_map.by_coordinate(pos).has_water()
-                    {
+                    if corridor.contains(&pos) && !touched.contains(&pos) {
                         touched.insert(pos);
                         unfinished.push(Unfinished(
                             i32::max_value() - pos.rectilinear_distance_to(coordinates[0]),
@@ -392,6 +786,54 @@ impl Map {
         }
     }
 
+    /// Makes a tributary flow towards where it touches the water it joins, by having each tile
+    /// flow to a neighbor closer to the junction.
+    fn set_tributary_flow(
+        &mut self,
+        corridor: &BTreeSet<Coordinate>,
+        claimed: &HashSet<Coordinate>,
+        generator: &mut dyn Generator,
+    ) {
+        let neighbors = |p: Coordinate| {
+            iproduct!(p.y - 1..=p.y + 1, p.x - 1..=p.x + 1)
+                .map(|(y, x)| Coordinate::new(x, y))
+                .filter(move |&n| n != p)
+        };
+
+        // The mouth of the tributary is the tiles next to the water it flows into
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        for &pos in corridor {
+            if let Some(joined) = neighbors(pos).find(|n| claimed.contains(n)) {
+                self.tile_map.set_flow(pos, pos.direction_to(joined));
+                distances.insert(pos, 0);
+                queue.push_back(pos);
+            }
+        }
+
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[&current] + 1;
+            for pos in neighbors(current) {
+                if corridor.contains(&pos) && !distances.contains_key(&pos) {
+                    distances.insert(pos, distance);
+                    queue.push_back(pos);
+                }
+            }
+        }
+
+        for &pos in corridor {
+            let distance = match distances.get(&pos) {
+                Some(&distance) if distance > 0 => distance,
+                _ => continue,
+            };
+            let downstream: Vec<_> = neighbors(pos)
+                .filter(|n| distances.get(n).map_or(false, |&d| d < distance))
+                .collect();
+            let next = generator.select(&downstream);
+            self.tile_map.set_flow(pos, pos.direction_to(next));
+        }
+    }
+
     /// Calculate flow for all ground tiles
     ///
     /// 'flow' is used for propagation of filth over time, and
@@ -402,14 +844,18 @@ impl Map {
     /// we choose to flow towards the river, by picking a random
     /// water tile and flowing toward it.
     fn set_ground_flow(&mut self, generator: &mut dyn Generator) {
-        for (y, x) in iproduct!(0..self.extent.height, 0..self.extent.width) {
+        let extent = self.extent;
+        let water_positions: Vec<_> = self.water_list.iter().map(|(_, w)| w.position).collect();
+        let flows = self.tile_map.flows_mut();
+        // The flow layer is stored row by row, so this goes through the tiles in the same order
+        for (index, flow) in flows.iter_mut().enumerate() {
+            let (x, y) = (index as i32 % extent.width, index as i32 / extent.width);
             let pos = Coordinate::new(x, y);
-            let tile = self.tile_map.by_coordinate_mut(pos);
-            if tile.flow == Direction::None {
+            if *flow == Direction::None {
                 let mut lowest = Coordinate::new(x, y);
                 for (iy, ix) in iproduct!(y - 1..=y + 1, x - 1..=x + 1) {
                     let candidate = Coordinate::new(ix, iy);
-                    if self.extent.is_inside(candidate)
+                    if extent.is_inside(candidate)
                         && self.height_map.get_value(ix, iy)
                             < self.height_map.get_value(lowest.x, lowest.y)
                     {
@@ -417,14 +863,12 @@ impl Map {
                     }
                 }
 
-                tile.flow = pos.direction_to(lowest);
+                *flow = pos.direction_to(lowest);
 
-                if tile.flow == Direction::None && !self.water_list.is_empty() {
+                if *flow == Direction::None && !water_positions.is_empty() {
                     // No slope here, so approximate towards river
-                    let random_water = generator.select_by_ref(&self.water_list[..]);
-                    let random_water = &*random_water.borrow();
-                    let coord = random_water.position;
-                    tile.flow = pos.direction_to(coord);
+                    let coord = generator.select(&water_positions);
+                    *flow = pos.direction_to(coord);
                 }
             }
         }
@@ -435,48 +879,51 @@ impl Map {
             render_data.viewport.size.width,
             render_data.viewport.size.height,
         );
-        for (y, x) in iproduct!(
-            up_left.y..up_left.y + mini_map.height(),
-            up_left.x..up_left.x + mini_map.width()
-        ) {
-            let xy = Coordinate::new(x, y);
-            let mini_map_position = xy - up_left;
-            if self.extent.is_inside(xy) {
-                let tile = self.tile_map.by_coordinate(xy);
-                tile.draw(&mut mini_map, mini_map_position);
-
-                if !self.overlays.contains(&Overlay::Terrain) {
-                    if let Some(water) = self.water(xy) {
-                        if water.depth() > 0 {
-                            water.draw(&mut mini_map, mini_map_position);
-                        }
-                    }
-                    if let Some(filth) = self.filth(xy) {
-                        if filth.depth() > 0 {
-                            filth.draw(&mut mini_map, mini_map_position);
-                        }
-                    }
-                    let nat_num = self.tile_map.by_coordinate(xy).nature_object_ref;
-                    if nat_num >= 0 {
-                        self.nature_list[&nat_num].draw(&mut mini_map, mini_map_position);
-                    }
+        let down_right = up_left + Coordinate::new(mini_map.width() - 1, mini_map.height() - 1);
+
+        // Only the chunks that changed since they were last drawn, or that just came into view,
+        // need to be drawn again
+        let visible: Vec<_> = self
+            .dirty_chunks
+            .chunks_within(up_left, down_right)
+            .collect();
+        let mut terrain_chunks = std::mem::replace(&mut self.terrain_chunks, HashMap::new());
+        terrain_chunks.retain(|chunk, _| visible.contains(chunk));
+        for &chunk in &visible {
+            let (low, high) = self.dirty_chunks.chunk_bounds(chunk);
+            let changed = self.dirty_chunks.take_render_dirty(chunk);
+            let cached = terrain_chunks.contains_key(&chunk);
+            let terrain = terrain_chunks
+                .entry(chunk)
+                .or_insert_with(|| Offscreen::new(CHUNK_SIZE, CHUNK_SIZE));
+            if changed || !cached {
+                for (y, x) in iproduct!(low.y..=high.y, low.x..=high.x) {
+                    self.draw_tile(Coordinate::new(x, y), terrain, low);
                 }
-                if self.overlays.contains(&Overlay::Territory) {
-                    mini_map.set_char_background(
-                        mini_map_position.into(),
-                        if self.extent.is_inside(mini_map_position)
-                            && self.tile_map.by_coordinate(mini_map_position).territory
-                        {
-                            Color::new(45, 85, 0)
-                        } else {
-                            Color::new(80, 0, 0)
-                        },
-                        BackgroundFlag::Default,
-                    )
-                }
-            } else {
+            }
+
+            // Only the part of the chunk inside the viewport gets copied onto it
+            let shown_low = low.max(up_left);
+            let shown_high = high.min(down_right);
+            if shown_low.inside_rectangle(low, shown_high) {
+                tcod::console::blit(
+                    &*terrain,
+                    (shown_low - low).into(),
+                    (shown_high - shown_low + 1).into(),
+                    &mut mini_map,
+                    (shown_low - up_left).into(),
+                    1.,
+                    1.,
+                );
+            }
+        }
+        self.terrain_chunks = terrain_chunks;
+
+        for (y, x) in iproduct!(up_left.y..=down_right.y, up_left.x..=down_right.x) {
+            let xy = Coordinate::new(x, y);
+            if !self.extent.is_inside(xy) {
                 mini_map.put_char_ex(
-                    (mini_map_position).into(),
+                    (xy - up_left).into(),
                     Chars::Block3.into(),
                     colors::BLACK,
                     colors::WHITE,
@@ -486,6 +933,52 @@ impl Map {
 
         mini_map
     }
+
+    /// Draws the tile at `p`, along with the water, filth and nature objects on it, onto the
+    /// buffer of the chunk whose top left corner is at `origin`.
+    fn draw_tile(&self, p: Coordinate, terrain: &mut Offscreen, origin: Coordinate) {
+        let at = p - origin;
+        self.tile_map.draw(p, terrain, at);
+
+        if !self.overlays.contains(&Overlay::Terrain) {
+            if let Some(water) = self.water(p) {
+                if water.depth() > 0 {
+                    water.draw(terrain, at);
+                }
+            }
+            if let Some(filth) = self.filth(p) {
+                if filth.depth() > 0 {
+                    filth.draw(terrain, at);
+                }
+            }
+            let nat_num = self.tile_map.nature_object(p);
+            if nat_num >= 0 {
+                self.nature_list[&nat_num].draw(terrain, at);
+            }
+        }
+        if self.overlays.contains(&Overlay::Territory) {
+            terrain.set_char_background(
+                at.into(),
+                if self.tile_map.is_territory(p) {
+                    Color::new(45, 85, 0)
+                } else {
+                    Color::new(80, 0, 0)
+                },
+                BackgroundFlag::Default,
+            )
+        }
+        if self.overlays.contains(&Overlay::Corruption) {
+            let corruption = self.tile_map.corruption(p);
+            if corruption > 0 {
+                let intensity = (corruption * 255 / TileMap::MAX_CORRUPTION).min(255) as u8;
+                terrain.set_char_background(
+                    p.into(),
+                    Color::new(intensity, 0, intensity / 2),
+                    BackgroundFlag::Default,
+                )
+            }
+        }
+    }
 }
 
 pub struct MapRenderData<'m> {
@@ -538,6 +1031,7 @@ where
 pub enum Overlay {
     Territory,
     Terrain,
+    Corruption,
 }
 
 fn update_stage_favor(
@@ -597,9 +1091,183 @@ impl<E: MapDrawable> EntityList<E> {
         Self(HashMap::new())
     }
 
+    pub fn insert(&mut self, uid: isize, entity: E) {
+        self.0.insert(uid, entity);
+    }
+
+    pub fn remove(&mut self, uid: isize) -> Option<E> {
+        self.0.remove(&uid)
+    }
+
+    pub fn get_mut(&mut self, uid: isize) -> Option<&mut E> {
+        self.0.get_mut(&uid)
+    }
+
     pub fn draw(&self, mini_map: &mut dyn SafeConsole, up_left: Coordinate) {
         for construction in self.0.values() {
             construction.draw(mini_map, up_left);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::random::DefaultGenerator;
+    use rand::rngs::StdRng;
+    use std::time::Instant;
+
+    /// A map of the given size covered in grass, along with the generator used to lay the grass,
+    /// seeded with `seed`.
+    pub(super) fn grass_map(size: Size, seed: u64) -> (Map, DefaultGenerator<StdRng>) {
+        let mut generator = DefaultGenerator::default_with_seed(seed);
+        let mut map = Map::new(size);
+        for (y, x) in iproduct!(0..size.height, 0..size.width) {
+            map.set_tile_type(Coordinate::new(x, y), TileType::Grass, &mut generator);
+        }
+
+        (map, generator)
+    }
+
+    const RIVER_Y: i32 = 20;
+    const TRIBUTARY_X: i32 = 20;
+
+    fn merging_rivers(generator: &mut dyn Generator) -> (Map, [RiverCurve; 2]) {
+        let mut map = Map::new(Size::new(40, 40));
+        for x in 0..40 {
+            let p = Coordinate::new(x, RIVER_Y);
+            map.add_water(p, WaterNode::new(p, WaterNode::RIVER_DEPTH, 0, generator));
+        }
+        for y in 0..RIVER_Y {
+            let p = Coordinate::new(TRIBUTARY_X, y);
+            map.add_water(p, WaterNode::new(p, WaterNode::RIVER_DEPTH, 0, generator));
+        }
+
+        let river = RiverCurve {
+            px: [0, 13, 26, 39],
+            py: [RIVER_Y; 4],
+            radius: 1.,
+            joins: None,
+        };
+        let tributary = RiverCurve {
+            px: [TRIBUTARY_X; 4],
+            py: [0, 6, 12, RIVER_Y - 1],
+            radius: 1.,
+            joins: Some(0),
+        };
+
+        (map, [river, tributary])
+    }
+
+    #[test]
+    fn tributaries_flow_into_the_river_they_join_without_changing_its_flow() {
+        let mut generator = DefaultGenerator::default_with_seed(8);
+        let (mut map, rivers) = merging_rivers(&mut generator);
+        map.calculate_flow(&rivers, &mut generator);
+
+        generator.reseed(8);
+        let (mut river_only, _) = merging_rivers(&mut generator);
+        river_only.calculate_flow(&rivers[..1], &mut generator);
+
+        for x in 0..40 {
+            let p = Coordinate::new(x, RIVER_Y);
+            assert_eq!(map.tile_map.flow(p), river_only.tile_map.flow(p));
+        }
+        // The tiles closest to the river are part of its own corridor, so only those further
+        // upstream are up to the tributary
+        for y in 0..RIVER_Y - 4 {
+            let p = Coordinate::new(TRIBUTARY_X, y);
+            assert_eq!(map.tile_map.flow(p), Direction::South, "at {:?}", p);
+        }
+    }
+
+    #[test]
+    fn only_water_that_actually_changed_is_redrawn() {
+        let (mut map, mut generator) = grass_map(Size::new(20, 12), 11);
+        let p = Coordinate::new(7, 4);
+        map.add_water(p, WaterNode::new(p, 10, 0, &mut generator));
+        map.dirty_chunks.drain_changed_tiles().count();
+
+        assert_eq!(map.modify_water(p, |water| water.depth()), Some(10));
+        assert_eq!(map.dirty_chunks.drain_changed_tiles().count(), 0);
+
+        map.modify_water(p, |water| water.set_filth(3));
+        assert_eq!(
+            map.dirty_chunks.drain_changed_tiles().collect::<Vec<_>>(),
+            [p]
+        );
+
+        assert_eq!(map.modify_water(Coordinate::new(8, 4), |_| ()), None);
+    }
+
+    #[test]
+    fn creatures_wear_down_the_tiles_they_move_onto() {
+        let (mut map, mut generator) = grass_map(Size::new(14, 10), 21);
+        let start = Coordinate::new(2, 3);
+        let step = Coordinate::new(3, 3);
+        let wall = Coordinate::new(4, 3);
+        map.tile_map.set_walkable(wall, false);
+        let goblin = map
+            .create_npc(start, "Goblin", 'g', Color::new(0, 255, 0))
+            .unwrap();
+
+        assert!(map.move_npc(goblin, step, &mut generator));
+        assert_eq!(map.tile_map.walked_over(step), 1);
+        assert_eq!(map.tile_map.walked_over(start), 0);
+
+        assert!(!map.move_npc(goblin, wall, &mut generator));
+        assert!(!map.move_npc(goblin, Coordinate::new(14, 3), &mut generator));
+        assert!(!map.move_npc(goblin + 1, start, &mut generator));
+        assert_eq!(map.npc_list.get(&goblin).unwrap().pos(), step);
+        assert_eq!(map.tile_map.walked_over(wall), 0);
+    }
+
+    /// Prints how long each pass over the whole of a 500x500 map takes, as the best of a few runs.
+    /// Run it with `cargo test --release whole_map_pass_timings -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn whole_map_pass_timings() {
+        const RUNS: usize = 5;
+
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let (mut map, mut generator) = grass_map(Size::new(500, 500), 10);
+        let coordinates: Vec<_> = iproduct!(0..500, 0..500)
+            .map(|(y, x)| Coordinate::new(x, y))
+            .collect();
+
+        let mut time = |name: &str, pass: &mut dyn FnMut(&mut Map, &mut dyn Generator)| {
+            let best = (0..RUNS)
+                .map(|_| {
+                    let start = Instant::now();
+                    pass(&mut map, &mut generator);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap();
+            println!("{:<24} {:>10.2?}", name, best);
+        };
+
+        time("reset every tile type", &mut |map, generator| {
+            for &p in &coordinates {
+                map.set_tile_type(p, TileType::Grass, generator);
+            }
+        });
+        time("update_cache, all dirty", &mut |map, _| {
+            for &p in &coordinates {
+                map.add_to_cache(p);
+            }
+            map.update_cache();
+        });
+        time("calculate_flow", &mut |map, generator| {
+            map.calculate_flow(&[], generator)
+        });
+        time("tile type counts", &mut |map, _| {
+            map.tile_type_counts();
+        });
+        time("naturify every tile", &mut |map, generator| {
+            for &p in &coordinates {
+                map.naturify(p, &presets, generator);
+            }
+        });
+    }
+}
@@ -0,0 +1,204 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::random::Generator;
+use crate::game::game_data::map::{Map, MapExtentHelper, TileMap};
+use crate::game::game_data::GameData;
+use crate::util::SlotHandle;
+use itertools::iproduct;
+use std::borrow::Cow;
+
+/// Something that keeps spreading corruption into the land around it, such as a cursed
+/// construction, a spell or an event.
+#[derive(Debug, Copy, Clone)]
+pub struct CorruptionSource {
+    pos: Coordinate,
+    magnitude: i32,
+}
+
+impl CorruptionSource {
+    pub fn new(pos: Coordinate, magnitude: i32) -> Self {
+        Self { pos, magnitude }
+    }
+
+    pub fn position(&self) -> Coordinate {
+        self.pos
+    }
+
+    /// How much corruption the source spreads each time it spreads.
+    pub fn magnitude(&self) -> i32 {
+        self.magnitude
+    }
+}
+
+/// Something that happens on the tiles corruption spreads to, such as creatures there falling
+/// sick or monsters spawning. Mods add their own with [`Map::add_corruption_effect`].
+pub trait CorruptionEffect {
+    /// The name the effect can be removed by.
+    fn name(&self) -> Cow<'_, str>;
+
+    /// Called for every tile left corrupted when corruption spreads to it.
+    fn affect(&self, map: &mut Map, p: Coordinate, generator: &mut dyn Generator);
+}
+
+impl Map {
+    /// The chance, one in this many, that a source spreads its corruption each tick.
+    const CORRUPTION_SPREAD_CHANCE: i32 = GameData::UPDATES_PER_SECOND;
+    /// The share, in percent, of what is left that spreading corruption loses with each step it
+    /// takes away from where it started.
+    const CORRUPTION_FALLOFF: i32 = 5;
+    /// Spreading corruption gives up after this many steps.
+    const MAX_CORRUPTION_STEPS: i32 = 2000;
+
+    /// Adds a source that keeps corrupting the land around it until it is removed.
+    pub fn add_corruption_source(
+        &mut self,
+        source: CorruptionSource,
+    ) -> SlotHandle<CorruptionSource> {
+        self.corruption_sources.insert(source)
+    }
+
+    pub fn remove_corruption_source(
+        &mut self,
+        source: SlotHandle<CorruptionSource>,
+    ) -> Option<CorruptionSource> {
+        self.corruption_sources.remove(source)
+    }
+
+    /// Every corruption source on the map, along with its handle.
+    pub fn corruption_sources(
+        &self,
+    ) -> impl Iterator<Item = (SlotHandle<CorruptionSource>, &CorruptionSource)> {
+        self.corruption_sources.iter()
+    }
+
+    pub fn add_corruption_effect(&mut self, effect: Box<dyn CorruptionEffect>) {
+        self.corruption_effects.push(effect);
+    }
+
+    pub fn remove_corruption_effect(&mut self, name: &str) -> Option<Box<dyn CorruptionEffect>> {
+        let index = self
+            .corruption_effects
+            .iter()
+            .position(|e| e.name() == name)?;
+        Some(self.corruption_effects.remove(index))
+    }
+
+    /// Lets every corruption source spread its corruption, now and then.
+    pub fn update_corruption(&mut self, generator: &mut dyn Generator) {
+        let sources: Vec<_> = self.corruption_sources.iter().map(|(_, s)| *s).collect();
+        for source in sources {
+            if generator.generate_integer_up_to(Self::CORRUPTION_SPREAD_CHANCE - 1) == 0 {
+                self.corrupt(source.position(), source.magnitude(), generator);
+            }
+        }
+    }
+
+    /// Spreads `magnitude` corruption out from `p`. The corruption wanders off in random steps,
+    /// filling up each tile it comes across before moving on and thinning out as it goes, so the
+    /// land closest to `p` is the most corrupted. Every corruption effect is applied to the tiles
+    /// left corrupted.
+    ///
+    /// Corrupt in original
+    pub fn corrupt(&mut self, p: Coordinate, magnitude: i32, generator: &mut dyn Generator) {
+        let mut p = self.extent.shrink(p);
+        let mut magnitude = magnitude;
+        let mut corrupted = vec![];
+        for _ in 0..Self::MAX_CORRUPTION_STEPS {
+            if magnitude <= 0 {
+                break;
+            }
+
+            let corruption = self.tile_map.corruption(p);
+            if corruption < TileMap::MAX_CORRUPTION {
+                let added = magnitude.min(TileMap::MAX_CORRUPTION - corruption);
+                self.tile_map.corrupt(p, added);
+                self.dirty_chunks.mark(p);
+                magnitude -= added;
+                if self.tile_map.is_corrupted(p) && !corrupted.contains(&p) {
+                    corrupted.push(p);
+                }
+            }
+            magnitude -= (magnitude * Self::CORRUPTION_FALLOFF / 100).max(1);
+
+            p = self
+                .extent
+                .shrink(generator.generate_coordinate_within_distance(p, 1));
+        }
+
+        if !self.corruption_effects.is_empty() {
+            // The effects get the whole map to work with, so they are set aside while they run
+            let effects = std::mem::take(&mut self.corruption_effects);
+            for &tile in &corrupted {
+                for effect in &effects {
+                    effect.affect(self, tile, generator);
+                }
+            }
+            self.corruption_effects.extend(effects);
+        }
+    }
+
+    /// Cleanses up to `magnitude` corruption from the tiles within `radius` of `p`, taking less
+    /// away the further they are from it.
+    pub fn purify(&mut self, p: Coordinate, radius: i32, magnitude: i32) {
+        let low = self.extent.shrink(p - radius);
+        let high = self.extent.shrink(p + radius);
+        for (y, x) in iproduct!(low.y..=high.y, low.x..=high.x) {
+            let tile = Coordinate::new(x, y);
+            let distance = p.straight_line_distance_to(tile);
+            if distance > radius as f32 || self.tile_map.corruption(tile) == 0 {
+                continue;
+            }
+            let falloff = 1. - distance / (radius + 1) as f32;
+            let cleansed = (magnitude as f32 * falloff).round() as i32;
+            self.tile_map.corrupt(tile, -cleansed);
+            self.dirty_chunks.mark(tile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+
+    #[test]
+    fn corruption_is_strongest_at_its_source_and_can_be_purified() {
+        let mut generator = DefaultGenerator::default_with_seed(11);
+        let mut map = Map::new(Size::new(40, 40));
+        let source = Coordinate::new(20, 20);
+
+        map.corrupt(source, 1000, &mut generator);
+        assert_eq!(map.tile_map.corruption(source), TileMap::MAX_CORRUPTION);
+        assert_eq!(map.tile_map.corruption(Coordinate::new(0, 0)), 0);
+        let total: i32 = iproduct!(0..40, 0..40)
+            .map(|(x, y)| map.tile_map.corruption(Coordinate::new(x, y)))
+            .sum();
+        assert!(total > TileMap::MAX_CORRUPTION && total <= 1000);
+
+        map.purify(source, 40, 1000);
+        let total: i32 = iproduct!(0..40, 0..40)
+            .map(|(x, y)| map.tile_map.corruption(Coordinate::new(x, y)))
+            .sum();
+        assert_eq!(total, 0);
+    }
+}
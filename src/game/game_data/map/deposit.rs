@@ -0,0 +1,188 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::minerals::Mineral;
+use crate::data::random::Generator;
+use crate::game::game_data::map::{Map, MapExtentHelper, TileType};
+use itertools::iproduct;
+
+/// Some amount of a mineral held by a rock tile.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Deposit {
+    /// The index of the mineral in the mineral table.
+    pub mineral: usize,
+    pub amount: u32,
+}
+
+impl Map {
+    pub fn deposit(&self, p: Coordinate) -> Option<Deposit> {
+        if self.extent.is_inside(p) {
+            self.tile_map.deposit(p)
+        } else {
+            None
+        }
+    }
+
+    /// Every deposit inside the rectangle from `low` to `high`, both inclusive.
+    pub fn deposits_within(
+        &self,
+        low: Coordinate,
+        high: Coordinate,
+    ) -> impl Iterator<Item = (Coordinate, Deposit)> + '_ {
+        let low = self.extent.shrink(low);
+        let high = self.extent.shrink(high);
+        iproduct!(low.y..=high.y, low.x..=high.x).filter_map(move |(y, x)| {
+            let p = Coordinate::new(x, y);
+            self.tile_map.deposit(p).map(|d| (p, d))
+        })
+    }
+
+    /// Takes up to `amount` of the deposit at `p`, removing the deposit once it runs out. Returns
+    /// how much was actually taken.
+    pub fn mine_deposit(&mut self, p: Coordinate, amount: u32) -> u32 {
+        if !self.extent.is_inside(p) {
+            return 0;
+        }
+
+        let deposit = self.tile_map.deposit_mut(p);
+        match deposit.as_mut() {
+            Some(held) => {
+                let taken = amount.min(held.amount);
+                held.amount -= taken;
+                if held.amount == 0 {
+                    *deposit = None;
+                }
+                taken
+            }
+            None => 0,
+        }
+    }
+
+    pub fn count_deposit_tiles(&self) -> usize {
+        self.tile_map
+            .deposits()
+            .iter()
+            .filter(|d| d.is_some())
+            .count()
+    }
+
+    /// Grows a cluster of `mineral` outwards from `origin` through rock tiles that don't already
+    /// hold a deposit.
+    pub(in crate::game::game_data) fn place_deposit_cluster(
+        &mut self,
+        origin: Coordinate,
+        mineral_index: usize,
+        mineral: &Mineral,
+        generator: &mut dyn Generator,
+    ) {
+        let size = generator
+            .generate_integer(mineral.cluster_size.0 as i32, mineral.cluster_size.1 as i32);
+
+        let mut frontier = vec![origin];
+        let mut placed = 0;
+        while placed < size && !frontier.is_empty() {
+            let index = generator.generate_integer_up_to(frontier.len() as i32 - 1) as usize;
+            let p = frontier.swap_remove(index);
+            if !self.extent.is_inside(p) {
+                continue;
+            }
+
+            if self.tile_map.tile_type(p) != TileType::Rock || self.tile_map.deposit(p).is_some() {
+                continue;
+            }
+            *self.tile_map.deposit_mut(p) = Some(Deposit {
+                mineral: mineral_index,
+                amount: generator.generate_integer(mineral.amount.0 as i32, mineral.amount.1 as i32)
+                    as u32,
+            });
+            placed += 1;
+
+            frontier.extend(
+                [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                    .iter()
+                    .map(|&d| p + Coordinate::from(d)),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::minerals::Minerals;
+    use crate::data::random::DefaultGenerator;
+
+    /// A map that is grass on the left half and rock on the right.
+    fn half_rock_map(generator: &mut dyn Generator) -> Map {
+        let mut map = Map::new(Size::new(16, 10));
+        for (y, x) in iproduct!(0..10, 8..16) {
+            map.set_tile_type(Coordinate::new(x, y), TileType::Rock, generator);
+        }
+        map
+    }
+
+    #[test]
+    fn clusters_grow_to_their_size_through_rock_only() {
+        let mut generator = DefaultGenerator::default_with_seed(21);
+        let minerals = Minerals::load_built_in().unwrap();
+        let iron = minerals.index_of("iron").unwrap();
+        let mut mineral = minerals.get(iron).unwrap().clone();
+        mineral.cluster_size = (12, 12);
+        mineral.amount = (4, 7);
+
+        let mut map = half_rock_map(&mut generator);
+        map.place_deposit_cluster(Coordinate::new(9, 5), iron, &mineral, &mut generator);
+
+        let deposits: Vec<_> = map
+            .deposits_within(Coordinate::ORIGIN, Coordinate::new(15, 9))
+            .collect();
+        assert_eq!(deposits.len(), 12);
+        for (p, deposit) in deposits {
+            assert_eq!(map.tile_type(p), TileType::Rock);
+            assert_eq!(deposit.mineral, iron);
+            assert!(deposit.amount >= 4 && deposit.amount <= 7);
+        }
+
+        // A cluster started on grass can't grow anywhere
+        map.place_deposit_cluster(Coordinate::new(2, 5), iron, &mineral, &mut generator);
+        assert_eq!(map.count_deposit_tiles(), 12);
+    }
+
+    #[test]
+    fn mining_runs_a_deposit_down_until_it_is_gone() {
+        let mut generator = DefaultGenerator::default_with_seed(22);
+        let minerals = Minerals::load_built_in().unwrap();
+        let mut mineral = minerals.all()[0].clone();
+        mineral.cluster_size = (1, 1);
+        mineral.amount = (10, 10);
+
+        let mut map = half_rock_map(&mut generator);
+        let p = Coordinate::new(12, 3);
+        map.place_deposit_cluster(p, 0, &mineral, &mut generator);
+
+        assert_eq!(map.mine_deposit(p, 4), 4);
+        assert_eq!(map.deposit(p).unwrap().amount, 6);
+        assert_eq!(map.mine_deposit(p, 100), 6);
+        assert_eq!(map.deposit(p), None);
+        assert_eq!(map.mine_deposit(p, 1), 0);
+        assert_eq!(map.mine_deposit(Coordinate::new(-1, 3), 1), 0);
+    }
+}
@@ -0,0 +1,224 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::base::Size;
+use crate::game::game_data::map::MapExtentHelper;
+use itertools::iproduct;
+
+/// The side length, in tiles, of the square chunks the map is split into.
+pub const CHUNK_SIZE: i32 = 16;
+
+const WORDS_PER_CHUNK: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize / 64;
+
+/// One bit for each tile of a chunk, row by row.
+type ChunkBits = [u64; WORDS_PER_CHUNK];
+
+/// Keeps track of which parts of the map have changed, by splitting the map into chunks of
+/// [`CHUNK_SIZE`] by [`CHUNK_SIZE`] tiles.
+///
+/// Changes are tracked separately for the tile cache, which needs to know exactly which tiles
+/// changed, and for drawing, which only needs to know which chunks to redraw.
+pub struct DirtyChunks {
+    extent: Size,
+    /// How many chunks there are across and down the map.
+    chunks: Size,
+    /// For each chunk, which of its tiles have changed since the cache was last updated.
+    tiles: Vec<ChunkBits>,
+    /// The chunks that have any bits set in `tiles`, in the order they were first changed.
+    cache_dirty: Vec<usize>,
+    /// For each chunk, whether it has changed since it was last drawn.
+    render_dirty: Vec<bool>,
+}
+
+impl DirtyChunks {
+    /// Creates the tracking for a map of the given `extent`. Every chunk starts out needing to be
+    /// drawn, since none of them have been drawn yet.
+    pub fn new(extent: Size) -> Self {
+        let chunks = Size::new(
+            (extent.width + CHUNK_SIZE - 1) / CHUNK_SIZE,
+            (extent.height + CHUNK_SIZE - 1) / CHUNK_SIZE,
+        );
+        let count = chunks.area() as usize;
+        Self {
+            extent,
+            chunks,
+            tiles: vec![[0; WORDS_PER_CHUNK]; count],
+            cache_dirty: vec![],
+            render_dirty: vec![true; count],
+        }
+    }
+
+    /// The index of the chunk holding `p`, which must be inside the map.
+    pub fn chunk_of(&self, p: Coordinate) -> usize {
+        ((p.y / CHUNK_SIZE) * self.chunks.width + p.x / CHUNK_SIZE) as usize
+    }
+
+    /// The top left and bottom right corners of `chunk`, both inclusive, cut off at the edges of
+    /// the map.
+    pub fn chunk_bounds(&self, chunk: usize) -> (Coordinate, Coordinate) {
+        let chunk = chunk as i32;
+        let low = Coordinate::new(
+            chunk % self.chunks.width * CHUNK_SIZE,
+            chunk / self.chunks.width * CHUNK_SIZE,
+        );
+        (low, self.extent.shrink(low + (CHUNK_SIZE - 1)))
+    }
+
+    /// The chunks overlapping the rectangle from `low` to `high`, both inclusive.
+    pub fn chunks_within(
+        &self,
+        low: Coordinate,
+        high: Coordinate,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let low = self.extent.shrink(low) / CHUNK_SIZE;
+        let high = self.extent.shrink(high) / CHUNK_SIZE;
+        iproduct!(low.y..=high.y, low.x..=high.x)
+            .map(move |(y, x)| (y * self.chunks.width + x) as usize)
+    }
+
+    /// Marks `p` as changed. Coordinates outside the map are ignored.
+    pub fn mark(&mut self, p: Coordinate) {
+        if !self.extent.is_inside(p) {
+            return;
+        }
+
+        let chunk = self.chunk_of(p);
+        let bit = ((p.y % CHUNK_SIZE) * CHUNK_SIZE + p.x % CHUNK_SIZE) as usize;
+        let bits = &mut self.tiles[chunk];
+        if bits.iter().all(|&word| word == 0) {
+            self.cache_dirty.push(chunk);
+        }
+        bits[bit / 64] |= 1 << (bit % 64);
+        self.render_dirty[chunk] = true;
+    }
+
+    /// Marks every tile in the rectangle from `low` to `high`, both inclusive, as changed.
+    pub fn mark_rectangle(&mut self, low: Coordinate, high: Coordinate) {
+        let low = self.extent.shrink(low);
+        let high = self.extent.shrink(high);
+        for (y, x) in iproduct!(low.y..=high.y, low.x..=high.x) {
+            self.mark(Coordinate::new(x, y));
+        }
+    }
+
+    /// Marks the whole map as changed, such as when the season changes.
+    pub fn mark_all(&mut self) {
+        self.cache_dirty = (0..self.tiles.len()).collect();
+        for bits in &mut self.tiles {
+            *bits = [!0; WORDS_PER_CHUNK];
+        }
+        for dirty in &mut self.render_dirty {
+            *dirty = true;
+        }
+    }
+
+    /// Marks every chunk as needing to be drawn again without touching the tile cache, such as
+    /// when an overlay is turned on or off.
+    pub fn mark_all_for_render(&mut self) {
+        for dirty in &mut self.render_dirty {
+            *dirty = true;
+        }
+    }
+
+    /// Takes every tile that changed since the last call, chunk by chunk.
+    pub fn drain_changed_tiles(&mut self) -> impl Iterator<Item = Coordinate> {
+        let tiles = &mut self.tiles;
+        let changed: Vec<_> = self
+            .cache_dirty
+            .drain(..)
+            .map(|chunk| {
+                (
+                    chunk,
+                    std::mem::replace(&mut tiles[chunk], [0; WORDS_PER_CHUNK]),
+                )
+            })
+            .collect();
+
+        let (extent, chunks_wide) = (self.extent, self.chunks.width);
+        changed.into_iter().flat_map(move |(chunk, bits)| {
+            let chunk = chunk as i32;
+            let origin = Coordinate::new(
+                chunk % chunks_wide * CHUNK_SIZE,
+                chunk / chunks_wide * CHUNK_SIZE,
+            );
+            (0..CHUNK_SIZE * CHUNK_SIZE)
+                .filter(move |&bit| bits[bit as usize / 64] & (1 << (bit % 64)) != 0)
+                .map(move |bit| origin + Coordinate::new(bit % CHUNK_SIZE, bit / CHUNK_SIZE))
+                .filter(move |&p| extent.is_inside(p))
+        })
+    }
+
+    /// Whether `chunk` has changed since it was last drawn. The chunk counts as drawn afterwards.
+    pub fn take_render_dirty(&mut self, chunk: usize) -> bool {
+        std::mem::replace(&mut self.render_dirty[chunk], false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_drained_once_chunk_by_chunk() {
+        let mut dirty = DirtyChunks::new(Size::new(40, 20));
+        dirty.mark(Coordinate::new(35, 3));
+        dirty.mark(Coordinate::new(1, 1));
+        dirty.mark(Coordinate::new(35, 3));
+        dirty.mark(Coordinate::new(2, 17));
+        dirty.mark(Coordinate::new(40, 0));
+
+        let changed: Vec<_> = dirty.drain_changed_tiles().collect();
+        assert_eq!(
+            changed,
+            vec![
+                Coordinate::new(35, 3),
+                Coordinate::new(1, 1),
+                Coordinate::new(2, 17),
+            ]
+        );
+        assert_eq!(dirty.drain_changed_tiles().count(), 0);
+
+        dirty.mark_all();
+        assert_eq!(dirty.drain_changed_tiles().count(), 40 * 20);
+    }
+
+    #[test]
+    fn chunks_are_drawn_only_when_changed() {
+        let mut dirty = DirtyChunks::new(Size::new(40, 20));
+        let all: Vec<_> = dirty
+            .chunks_within(Coordinate::ORIGIN, Coordinate::new(39, 19))
+            .collect();
+        assert_eq!(all.len(), 6);
+        assert!(all.iter().all(|&c| dirty.take_render_dirty(c)));
+        assert!(all.iter().all(|&c| !dirty.take_render_dirty(c)));
+
+        dirty.mark(Coordinate::new(20, 18));
+        let chunk = dirty.chunk_of(Coordinate::new(20, 18));
+        assert_eq!(
+            dirty.chunk_bounds(chunk),
+            (Coordinate::new(16, 16), Coordinate::new(31, 19))
+        );
+        assert!(dirty.take_render_dirty(chunk));
+        assert_eq!(
+            all.iter().filter(|&&c| dirty.take_render_dirty(c)).count(),
+            0
+        );
+    }
+}
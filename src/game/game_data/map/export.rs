@@ -0,0 +1,210 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::game::game_data::map::{Map, MapExtentHelper, MapGraphicDrawable, TileType};
+use crate::game::game_data::map_generation::RiverCurve;
+use itertools::iproduct;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use tcod::Color;
+
+/// Statistics about a generated map, meant for judging changes to the map generator.
+#[derive(Serialize, Debug)]
+pub struct MapSummary {
+    pub seed: u64,
+    pub width: i32,
+    pub height: i32,
+    pub tile_counts: BTreeMap<String, usize>,
+    pub water_tiles: usize,
+    pub rivers: usize,
+    pub tributaries: usize,
+    pub lakes: usize,
+    pub river_length: f64,
+    pub bog_size: usize,
+    pub deposit_tiles: usize,
+}
+
+impl Map {
+    /// Counts how many tiles there are of each tile type.
+    pub fn tile_type_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = HashMap::new();
+        for &tile_type in self.tile_map.tile_types() {
+            *counts.entry(tile_type).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|(tile_type, count)| (format!("{:?}", tile_type), count))
+            .collect()
+    }
+
+    pub fn count_tiles_of_type(&self, tile_type: TileType) -> usize {
+        self.tile_map
+            .tile_types()
+            .iter()
+            .filter(|&&t| t == tile_type)
+            .count()
+    }
+
+    /// How far `river` runs through water on the map, in tiles. Stretches of the curve that run
+    /// off the map or that were dug out but never filled with water aren't counted, so this is the
+    /// length of the river as it ended up on the map.
+    pub fn river_length(&self, river: &RiverCurve) -> f64 {
+        let segments = (river.length().ceil() as i32).max(1);
+
+        let mut length = 0.;
+        let mut previous = river.point_at(0.);
+        for segment in 1..=segments {
+            let current = river.point_at(f64::from(segment) / f64::from(segments));
+            let middle = Coordinate::new(
+                ((previous.0 + current.0) / 2.) as i32,
+                ((previous.1 + current.1) / 2.) as i32,
+            );
+            if self.extent.is_inside(middle) && self.water(middle).map_or(false, |w| w.depth() > 0)
+            {
+                length += (current.0 - previous.0).hypot(current.1 - previous.1);
+            }
+            previous = current;
+        }
+
+        length
+    }
+
+    pub fn count_water_tiles(&self) -> usize {
+        iproduct!(0..self.extent.height, 0..self.extent.width)
+            .filter(|&(y, x)| {
+                self.water(Coordinate::new(x, y))
+                    .map_or(false, |w| w.depth() > 0)
+            })
+            .count()
+    }
+
+    /// Writes the tile map, with water drawn on top of it, as a binary PPM image where each tile
+    /// is one pixel.
+    pub fn write_tile_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.write_ppm(writer, |map, p| {
+            if let Some(water) = map.water(p) {
+                if water.depth() > 0 {
+                    return water.fore_color();
+                }
+            }
+            map.tile_map.fore_color(p)
+        })
+    }
+
+    /// Writes the height map as a binary PPM image, going from black at the lowest point of the
+    /// map to white at the highest.
+    pub fn write_height_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (mut low, mut high) = (std::f32::MAX, std::f32::MIN);
+        for (y, x) in iproduct!(0..self.extent.height, 0..self.extent.width) {
+            let height = self.height_map.get_value(x, y);
+            low = low.min(height);
+            high = high.max(height);
+        }
+        let range = (high - low).max(std::f32::EPSILON);
+
+        self.write_ppm(writer, |map, p| {
+            let value = ((map.height_map.get_value(p.x, p.y) - low) / range * 255.) as u8;
+            Color::new(value, value, value)
+        })
+    }
+
+    fn write_ppm<W: Write, F: Fn(&Self, Coordinate) -> Color>(
+        &self,
+        writer: &mut W,
+        color_at: F,
+    ) -> io::Result<()> {
+        write!(
+            writer,
+            "P6\n{} {}\n255\n",
+            self.extent.width, self.extent.height
+        )?;
+        for (y, x) in iproduct!(0..self.extent.height, 0..self.extent.width) {
+            let color = color_at(self, Coordinate::new(x, y));
+            writer.write_all(&[color.r, color.g, color.b])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::water_node::WaterNode;
+
+    const HEADER: &[u8] = b"P6\n4 3\n255\n";
+
+    #[test]
+    fn tile_images_have_one_pixel_per_tile_with_water_drawn_on_top() {
+        let mut generator = DefaultGenerator::default_with_seed(3);
+        let mut map = Map::new(Size::new(4, 3));
+        let pond = Coordinate::new(2, 1);
+        map.add_water(pond, WaterNode::new(pond, 5, 0, &mut generator));
+
+        let mut image = vec![];
+        map.write_tile_ppm(&mut image).unwrap();
+
+        assert!(image.starts_with(HEADER));
+        assert_eq!(image.len(), HEADER.len() + 4 * 3 * 3);
+        let pixel = HEADER.len() + (pond.y * 4 + pond.x) as usize * 3;
+        let color = map.water(pond).unwrap().fore_color();
+        assert_eq!(&image[pixel..pixel + 3], &[color.r, color.g, color.b]);
+    }
+
+    #[test]
+    fn height_images_go_from_black_at_the_bottom_to_white_at_the_top() {
+        let mut map = Map::new(Size::new(4, 3));
+        map.height_map.set_value(0, 0, -2.);
+        map.height_map.set_value(3, 2, 6.);
+
+        let mut image = vec![];
+        map.write_height_ppm(&mut image).unwrap();
+
+        let pixels = &image[HEADER.len()..];
+        assert_eq!(&pixels[..3], &[0, 0, 0]);
+        assert_eq!(&pixels[pixels.len() - 3..], &[255, 255, 255]);
+    }
+
+    #[test]
+    fn only_the_stretches_of_a_river_with_water_in_them_count_towards_its_length() {
+        let mut generator = DefaultGenerator::default_with_seed(3);
+        let mut map = Map::new(Size::new(40, 10));
+        let river = RiverCurve {
+            px: [0, 10, 20, 30],
+            py: [5, 5, 5, 5],
+            radius: 1.,
+            joins: None,
+        };
+        assert_eq!(map.river_length(&river), 0.);
+
+        for x in 0..15 {
+            let p = Coordinate::new(x, 5);
+            map.add_water(p, WaterNode::new(p, 5, 0, &mut generator));
+        }
+
+        let length = map.river_length(&river);
+        assert!((length - 15.).abs() < 1.5, "length was {}", length);
+        assert_eq!(map.count_water_tiles(), 15);
+    }
+}
@@ -0,0 +1,194 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::random::Generator;
+use crate::game::game_data::filth_node::FilthNode;
+use crate::game::game_data::map::{Map, MapExtentHelper, TileFlags};
+
+impl Map {
+    /// How many tiles filth may spill across before the rest of it is given up on.
+    const MAX_FILTH_SPILL: i32 = 1000;
+    /// The chance, one in this many, that a filth node sheds some of its filth downhill each tick.
+    const FILTH_FLOW_CHANCE: i32 = 250;
+
+    /// Puts `amount` of filth on the tile at `p`. Water takes in any amount of filth, but a dry
+    /// tile only holds up to [`FilthNode::MAX_DEPTH`], and the rest spills over downhill, following
+    /// the flow of the ground. Filth passes over tiles that block water or can't be walked on, such
+    /// as walls, without settling there.
+    ///
+    /// CreateFilth in original
+    pub fn create_filth(
+        &mut self,
+        mut p: Coordinate,
+        mut amount: i32,
+        generator: &mut dyn Generator,
+    ) {
+        if !self.extent.is_inside(p) {
+            return;
+        }
+
+        let mut spills = 0;
+        while amount > 0 && spills < Self::MAX_FILTH_SPILL {
+            spills += 1;
+
+            if self
+                .modify_water(p, |water| water.add_filth(amount))
+                .is_some()
+            {
+                return;
+            }
+
+            if self.holds_filth(p) {
+                if let Some(handle) = self.tile_map.filth(p) {
+                    let filth = self.filth_list.get_mut(handle).unwrap();
+                    let added = (FilthNode::MAX_DEPTH - filth.depth()).max(0).min(amount);
+                    filth.set_depth(filth.depth() + added);
+                    amount -= added;
+                } else {
+                    let depth = amount.min(FilthNode::MAX_DEPTH);
+                    let handle = self.filth_list.insert(FilthNode::new(p, depth, generator));
+                    self.tile_map.set_filth(p, Some(handle));
+                    amount -= depth;
+                }
+                self.dirty_chunks.mark(p);
+            }
+
+            if amount > 0 {
+                p = self.next_filth_spill(p, spills, generator);
+            }
+        }
+    }
+
+    /// Whether filth can settle on the tile at `p`.
+    fn holds_filth(&self, p: Coordinate) -> bool {
+        let flags = self.tile_map.flags(p);
+        flags.contains(TileFlags::WALKABLE) && !flags.contains(TileFlags::BLOCKS_WATER)
+    }
+
+    /// Picks where filth overflowing at `p` spills to. Filth that has already spilled a long way
+    /// spreads out faster.
+    fn next_filth_spill(
+        &self,
+        p: Coordinate,
+        spills: i32,
+        generator: &mut dyn Generator,
+    ) -> Coordinate {
+        let distance = (spills / 100).max(1);
+        let mut offset = Coordinate::from(self.tile_map.flow(p)) * distance;
+        // Fan out sideways from the direction of the flow
+        if offset.x == 0 {
+            offset.x = generator.generate_integer(-distance, distance);
+        }
+        if offset.y == 0 {
+            offset.y = generator.generate_integer(-distance, distance);
+        }
+
+        let mut next = self.extent.shrink(p + offset);
+        if next == p {
+            next = self
+                .extent
+                .shrink(generator.generate_coordinate_within_rectangle(p - 1, p + 1));
+        }
+
+        next
+    }
+
+    /// Lets filth creep downhill for one tick. Now and then, a filth node deeper than the
+    /// shallowest filth sheds one unit of it onto the tile it flows towards.
+    pub fn update_filth(&mut self, generator: &mut dyn Generator) {
+        let shedding: Vec<_> = self
+            .filth_list
+            .iter()
+            .filter(|(_, filth)| filth.depth() > 1)
+            .map(|(handle, _)| handle)
+            .collect();
+
+        for handle in shedding {
+            if generator.generate_integer_up_to(Self::FILTH_FLOW_CHANCE - 1) != 0 {
+                continue;
+            }
+
+            let filth = self.filth_list.get_mut(handle).unwrap();
+            let p = filth.position();
+            filth.set_depth(filth.depth() - 1);
+            self.dirty_chunks.mark(p);
+
+            let next = self.next_filth_spill(p, 1, generator);
+            self.create_filth(next, 1, generator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::Direction;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::water_node::WaterNode;
+
+    #[test]
+    fn filth_spills_over_downhill_and_mixes_into_water() {
+        let mut generator = DefaultGenerator::default_with_seed(14);
+        let mut map = Map::new(Size::new(16, 22));
+        let p = Coordinate::new(5, 10);
+        map.tile_map.set_flow(p, Direction::East);
+
+        map.create_filth(p, 2 * FilthNode::MAX_DEPTH, &mut generator);
+
+        assert_eq!(map.filth(p).unwrap().depth(), FilthNode::MAX_DEPTH);
+        let spilled: Vec<_> = map
+            .filth_nodes()
+            .map(|(_, filth)| filth)
+            .filter(|filth| filth.position() != p)
+            .collect();
+        assert_eq!(spilled.len(), 1);
+        assert_eq!(spilled[0].position().x, p.x + 1);
+        assert_eq!(spilled[0].depth(), FilthNode::MAX_DEPTH);
+
+        let water = map.add_water(p, WaterNode::new(p, 10, 1, &mut generator));
+        assert!(map.filth(p).is_none());
+        assert_eq!(map.water_node(water).unwrap().filth(), FilthNode::MAX_DEPTH);
+        map.create_filth(p, 3, &mut generator);
+        assert_eq!(
+            map.water_node(water).unwrap().filth(),
+            FilthNode::MAX_DEPTH + 3
+        );
+    }
+
+    #[test]
+    fn filth_spills_past_walls_without_settling_on_them() {
+        let mut generator = DefaultGenerator::default_with_seed(14);
+        let mut map = Map::new(Size::new(12, 7));
+        let p = Coordinate::new(3, 3);
+        let wall = Coordinate::new(4, 3);
+        map.tile_map.set_flow(p, Direction::East);
+        map.tile_map.set_flow(wall, Direction::East);
+        map.tile_map.set_walkable(wall, false);
+        map.tile_map.set_blocks_water(wall, true);
+
+        map.create_filth(p, FilthNode::MAX_DEPTH + 2, &mut generator);
+
+        assert!(map.filth(wall).is_none());
+        let total: i32 = map.filth_nodes().map(|(_, filth)| filth.depth()).sum();
+        assert_eq!(total, FilthNode::MAX_DEPTH + 2);
+    }
+}
@@ -18,17 +18,377 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::game::game_data::map::MapGraphicDrawable;
-use tcod::Color;
+use crate::coordinate::{Coordinate, Direction};
+use crate::data::random::Generator;
+use crate::game::game_data::map::{
+    Map, MapExtentHelper, MapGraphicDrawable, NatureObject, TileType,
+};
+use crate::util::tcod::Chars;
+use crate::util::SlotHandle;
+use tcod::{colors, Color};
 
-pub struct FireNode {}
+#[derive(Debug, Copy, Clone)]
+pub struct FireNode {
+    pos: Coordinate,
+    temperature: i32,
+    graphic: char,
+    color: Color,
+}
+
+impl FireNode {
+    pub fn new(pos: Coordinate, temperature: i32, generator: &mut dyn Generator) -> Self {
+        let mut fire = Self {
+            pos,
+            temperature,
+            graphic: Chars::Block1.into(),
+            color: colors::FLAME,
+        };
+        fire.flicker(generator);
+
+        fire
+    }
+
+    pub fn position(&self) -> Coordinate {
+        self.pos
+    }
+
+    /// How much longer the fire will burn. The fire goes out once this reaches 0.
+    pub fn temperature(&self) -> i32 {
+        self.temperature
+    }
+
+    pub fn add_heat(&mut self, heat: i32) {
+        self.temperature = (self.temperature + heat).max(0);
+    }
+
+    fn flicker(&mut self, generator: &mut dyn Generator) {
+        self.graphic = generator
+            .select(&[Chars::Block1, Chars::Block2, Chars::Block3])
+            .into();
+        self.color = Color::new(
+            generator.generate_integer(225, 255) as u8,
+            generator.generate_integer(0, 250) as u8,
+            0,
+        );
+    }
+}
 
 impl MapGraphicDrawable for FireNode {
     fn graphic(&self) -> char {
-        unimplemented!()
+        self.graphic
     }
 
     fn fore_color(&self) -> Color {
-        unimplemented!()
+        self.color
+    }
+}
+
+/// Smoke rising from a fire, which drifts with the wind until it thins out.
+#[derive(Debug, Copy, Clone)]
+pub struct SmokeNode {
+    pos: Coordinate,
+    age: i32,
+    color: Color,
+}
+
+impl SmokeNode {
+    /// How many ticks smoke lingers before it has thinned out completely.
+    const LIFETIME: i32 = 100;
+
+    pub fn position(&self) -> Coordinate {
+        self.pos
+    }
+}
+
+impl MapGraphicDrawable for SmokeNode {
+    fn graphic(&self) -> char {
+        if self.age < Self::LIFETIME / 2 {
+            Chars::Block2.into()
+        } else {
+            Chars::Block1.into()
+        }
+    }
+
+    fn fore_color(&self) -> Color {
+        self.color
+    }
+}
+
+impl Map {
+    /// The temperature of a fire that has spread from a neighboring tile.
+    const FIRE_SPREAD_TEMPERATURE: i32 = 10;
+    /// The chance, one in this many, that a fire spreads each tick.
+    const FIRE_SPREAD_CHANCE: i32 = 8;
+    /// Out of every this many times a fire spreads, it spreads against the wind only once.
+    const FIRE_UPWIND_CHANCE: i32 = 4;
+    /// The chance, one in this many, that a fire cools down and scorches its tile each tick.
+    const FIRE_COOLING_CHANCE: i32 = 10;
+    /// The chance, one in this many, that a fire burns up the nature object on its tile each tick.
+    const FIRE_FUEL_CHANCE: i32 = 20;
+    /// How much hotter a fire gets from burning up a tree. Other nature objects give half as much.
+    const TREE_FUEL: i32 = 40;
+    /// The chance, one in this many, that rain cools a fire down each tick.
+    const RAIN_DOUSE_CHANCE: i32 = 5;
+    /// The chance, one in this many, that a fire gives off smoke each tick.
+    const SMOKE_CHANCE: i32 = 20;
+    /// The chance, one in this many, that smoke drifts along with the wind each tick.
+    const SMOKE_DRIFT_CHANCE: i32 = 3;
+    /// How burnt grass can get before there's nothing left on it to burn.
+    const MAX_BURNT: i32 = 10;
+
+    pub fn fire(&self, p: Coordinate) -> Option<&FireNode> {
+        self.tile_map.fire(p).and_then(|f| self.fire_list.get(f))
+    }
+
+    /// Every fire on the map, along with its handle.
+    pub fn fire_nodes(&self) -> impl Iterator<Item = (SlotHandle<FireNode>, &FireNode)> {
+        self.fire_list.iter()
+    }
+
+    pub fn smoke_nodes(&self) -> impl Iterator<Item = &SmokeNode> {
+        self.smoke_list.iter()
+    }
+
+    /// Whether there is anything on the tile at `p` that could catch fire: grass that isn't burnt
+    /// out yet or a nature object, and no water. Ice doesn't burn, and keeps the grass under it
+    /// from burning as well.
+    pub fn is_flammable(&self, p: Coordinate) -> bool {
+        if !self.extent.is_inside(p) || self.water(p).map_or(false, |w| w.depth() > 0) {
+            return false;
+        }
+
+        match self.nature_object(p) {
+            Some(nature_object) => !nature_object.is_ice(),
+            None => {
+                self.tile_map.tile_type(p) == TileType::Grass
+                    && self.tile_map.burnt(p) < Self::MAX_BURNT
+            }
+        }
+    }
+
+    /// Sets the tile at `p` on fire, or makes the fire already there hotter. Returns whether
+    /// there is a fire on the tile afterwards.
+    ///
+    /// CreateFire in original
+    pub fn start_fire(
+        &mut self,
+        p: Coordinate,
+        temperature: i32,
+        generator: &mut dyn Generator,
+    ) -> bool {
+        if let Some(fire) = self.tile_map.fire(p) {
+            self.fire_list.get_mut(fire).unwrap().add_heat(temperature);
+            return true;
+        }
+        if temperature <= 0 || !self.is_flammable(p) {
+            return false;
+        }
+
+        let fire = self
+            .fire_list
+            .insert(FireNode::new(p, temperature, generator));
+        self.tile_map.set_fire(p, Some(fire));
+        self.dirty_chunks.mark(p);
+
+        true
+    }
+
+    /// Cools the fire at `p` down by `amount`, such as when water is thrown on it, putting it out
+    /// if it gets cold enough. Returns whether the fire went out.
+    pub fn douse_fire(&mut self, p: Coordinate, amount: i32) -> bool {
+        let fire = match self
+            .tile_map
+            .fire(p)
+            .and_then(|f| self.fire_list.get_mut(f))
+        {
+            Some(fire) => fire,
+            None => return false,
+        };
+
+        fire.add_heat(-amount);
+        if fire.temperature() == 0 {
+            self.extinguish_fire(p);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Puts out the fire at `p` at once.
+    pub fn extinguish_fire(&mut self, p: Coordinate) -> Option<FireNode> {
+        let fire = self.tile_map.fire(p)?;
+        self.tile_map.set_fire(p, None);
+        self.dirty_chunks.mark(p);
+        self.fire_list.remove(fire)
+    }
+
+    /// Lets every fire burn for one tick, and lets the smoke drift.
+    pub fn update_fire(&mut self, generator: &mut dyn Generator) {
+        let fires: Vec<_> = self.fire_list.iter().map(|(handle, _)| handle).collect();
+        for fire in fires {
+            self.update_fire_node(fire, generator);
+        }
+
+        self.update_smoke(generator);
+    }
+
+    fn update_fire_node(&mut self, handle: SlotHandle<FireNode>, generator: &mut dyn Generator) {
+        let p = {
+            let fire = self.fire_list.get_mut(handle).unwrap();
+            fire.flicker(generator);
+            fire.position()
+        };
+
+        // Water puts fire out, but boils off some of the water doing so
+        if self.water(p).map_or(false, |w| w.depth() > 0) {
+            self.modify_water(p, |water| {
+                let new_depth = water.depth() - 1;
+                water.set_depth(new_depth, generator);
+            });
+            self.extinguish_fire(p);
+            return;
+        }
+
+        let mut heat = 0;
+        if self.weather.is_raining()
+            && generator.generate_integer_up_to(Self::RAIN_DOUSE_CHANCE - 1) == 0
+        {
+            heat -= 1;
+        }
+
+        if generator.generate_integer_up_to(Self::FIRE_COOLING_CHANCE - 1) == 0 {
+            heat -= 1;
+            self.tile_map.burn(p, 1);
+            self.dirty_chunks.mark(p);
+        }
+
+        // Ice doesn't burn, and burning it up would lose the water it was frozen from
+        let nature_object = self
+            .nature_object(p)
+            .filter(|n| !n.is_ice())
+            .map(NatureObject::uid);
+        if nature_object.is_some()
+            && generator.generate_integer_up_to(Self::FIRE_FUEL_CHANCE - 1) == 0
+        {
+            if let Some(nature_object) = nature_object.and_then(|n| self.remove_nature_object(n)) {
+                heat += if nature_object.is_tree() {
+                    Self::TREE_FUEL
+                } else {
+                    Self::TREE_FUEL / 2
+                };
+            }
+        }
+
+        // Burnt out grass with nothing else on it has nothing left to keep the fire going
+        if !self.is_flammable(p) {
+            heat -= 1;
+        }
+
+        if generator.generate_integer_up_to(Self::FIRE_SPREAD_CHANCE - 1) == 0 {
+            let direction = if generator.generate_integer_up_to(Self::FIRE_UPWIND_CHANCE - 1) == 0 {
+                generator.auto_select::<Direction>()
+            } else {
+                self.weather.wind_direction()
+            };
+            let target = p + Coordinate::from(direction);
+            if self.can_spread_fire_to(target) {
+                self.start_fire(target, Self::FIRE_SPREAD_TEMPERATURE, generator);
+            }
+        }
+
+        if generator.generate_integer_up_to(Self::SMOKE_CHANCE - 1) == 0 {
+            self.smoke_list.push(SmokeNode {
+                pos: p,
+                age: 0,
+                color: Color::new(
+                    generator.generate_integer(60, 100) as u8,
+                    generator.generate_integer(60, 100) as u8,
+                    generator.generate_integer(60, 100) as u8,
+                ),
+            });
+        }
+
+        let fire = self.fire_list.get_mut(handle).unwrap();
+        fire.add_heat(heat);
+        if fire.temperature() == 0 {
+            self.extinguish_fire(p);
+        }
+    }
+
+    /// Whether `p` is on the map and not already on fire.
+    fn can_spread_fire_to(&self, p: Coordinate) -> bool {
+        self.extent.is_inside(p) && self.tile_map.fire(p).is_none()
+    }
+
+    fn update_smoke(&mut self, generator: &mut dyn Generator) {
+        let wind = Coordinate::from(self.weather.wind_direction());
+        let extent = self.extent;
+        for smoke in &mut self.smoke_list {
+            smoke.age += 1;
+            if generator.generate_integer_up_to(Self::SMOKE_DRIFT_CHANCE - 1) == 0 {
+                smoke.pos += wind
+                    + Coordinate::new(
+                        generator.generate_integer(-1, 1),
+                        generator.generate_integer(-1, 1),
+                    );
+            }
+        }
+        self.smoke_list
+            .retain(|smoke| smoke.age < SmokeNode::LIFETIME && extent.is_inside(smoke.pos));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::nature_objects::NatureObjectPresets;
+    use crate::game::game_data::map::tests::grass_map;
+    use crate::game::game_data::water_node::WaterNode;
+    use itertools::iproduct;
+
+    #[test]
+    fn fire_only_burns_where_there_is_fuel_and_no_water() {
+        let (mut map, mut generator) = grass_map(Size::new(18, 24), 16);
+        let grass = Coordinate::new(5, 5);
+        let water = Coordinate::new(10, 10);
+        let rock = Coordinate::new(15, 15);
+        map.add_water(water, WaterNode::new(water, 10, 1, &mut generator));
+        map.set_tile_type(rock, TileType::Rock, &mut generator);
+
+        assert!(map.start_fire(grass, 10, &mut generator));
+        assert!(!map.start_fire(water, 10, &mut generator));
+        assert!(!map.start_fire(rock, 10, &mut generator));
+        assert_eq!(map.fire_nodes().count(), 1);
+
+        assert!(map.start_fire(grass, 5, &mut generator));
+        assert_eq!(map.fire(grass).unwrap().temperature(), 15);
+        assert!(!map.douse_fire(grass, 10));
+        assert!(map.douse_fire(grass, 10));
+        assert!(map.fire(grass).is_none());
+        assert_eq!(map.fire_nodes().count(), 0);
+    }
+
+    #[test]
+    fn fire_next_to_ice_leaves_it_frozen() {
+        let (mut map, mut generator) = grass_map(Size::new(11, 9), 19);
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let ice = presets.index_of("ice").unwrap();
+        let pond = Coordinate::new(4, 4);
+        map.add_water(pond, WaterNode::new(pond, 3, 1, &mut generator));
+        assert!(map.freeze_water(pond, ice, &presets));
+
+        assert!(!map.is_flammable(pond));
+        assert!(!map.start_fire(pond, 10, &mut generator));
+        for (y, x) in iproduct!(3..=5, 3..=5) {
+            map.start_fire(Coordinate::new(x, y), 1000, &mut generator);
+        }
+        for _ in 0..500 {
+            map.update_fire(&mut generator);
+        }
+
+        let frozen = map.nature_object(pond).unwrap();
+        assert!(frozen.is_ice());
+        assert_eq!(frozen.frozen_water().unwrap().depth(), 3);
     }
 }
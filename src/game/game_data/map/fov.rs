@@ -0,0 +1,225 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::base::Size;
+use crate::game::game_data::construction::Tag;
+use crate::game::game_data::map::{ConstructionHelper, Map, MapExtentHelper};
+use tcod::line::Line;
+
+/// What one faction can see of the map: the tiles in sight of its creatures right now, and every
+/// tile it has ever had in sight.
+pub struct FactionVisibility {
+    extent: Size,
+    visible: Vec<bool>,
+    seen: Vec<bool>,
+}
+
+impl FactionVisibility {
+    pub fn new(extent: Size) -> Self {
+        let area = extent.area() as usize;
+        Self {
+            extent,
+            visible: vec![false; area],
+            seen: vec![false; area],
+        }
+    }
+
+    fn index(&self, p: Coordinate) -> usize {
+        (p.y * self.extent.width + p.x) as usize
+    }
+
+    /// Whether the tile at `p` is in sight right now.
+    pub fn is_visible(&self, p: Coordinate) -> bool {
+        self.extent.is_inside(p) && self.visible[self.index(p)]
+    }
+
+    /// Whether the tile at `p` has ever been in sight, such as for lifting the fog of war.
+    pub fn has_seen(&self, p: Coordinate) -> bool {
+        self.extent.is_inside(p) && self.seen[self.index(p)]
+    }
+
+    fn hide_all(&mut self) {
+        for visible in &mut self.visible {
+            *visible = false;
+        }
+    }
+
+    fn reveal(&mut self, p: Coordinate) {
+        let i = self.index(p);
+        self.visible[i] = true;
+        self.seen[i] = true;
+    }
+}
+
+impl Map {
+    /// Whether the tile at `p` stops sight from passing through it, either by being solid itself,
+    /// like the void off the edge of the map, or by what stands on it, such as a tree or a wall.
+    ///
+    /// BlocksLight in original
+    pub fn blocks_light(&self, p: Coordinate) -> bool {
+        if !self.extent.is_inside(p) || self.tile_map.blocks_light(p) {
+            return true;
+        }
+
+        // TODO: Let sight through doors while they are open, once they can be opened
+        [
+            &self.static_construction_list,
+            &self.dynamic_construction_list,
+        ]
+        .construction(self.tile_map.construction(p))
+        .map_or(false, |c| c.has_tag(Tag::Wall) || c.has_tag(Tag::Door))
+    }
+
+    /// Whether `b` can be seen from `a`. The tiles between them must not block sight, but `b`
+    /// itself may, so that a wall or a tree can be seen even though nothing behind it can.
+    ///
+    /// LineOfSight in original
+    pub fn line_of_sight(&self, a: Coordinate, b: Coordinate) -> bool {
+        let mut line = Line::new(a.into(), b.into());
+        while let Some(p) = line.step() {
+            let p = Coordinate::from(p);
+            if p == b {
+                break;
+            }
+            if self.blocks_light(p) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Every tile that can be seen from `origin` within `radius` tiles, found by looking along
+    /// lines out to the edge of the radius until something blocks the view. Nothing can be seen
+    /// with a negative radius, not even `origin` itself.
+    pub fn field_of_view(&self, origin: Coordinate, radius: i32) -> Vec<Coordinate> {
+        if radius < 0 || !self.extent.is_inside(origin) {
+            return vec![];
+        }
+
+        let side = 2 * radius + 1;
+        let mut in_view = vec![false; (side * side) as usize];
+        let local = |p: Coordinate| {
+            let offset = p - origin + radius;
+            (offset.y * side + offset.x) as usize
+        };
+        in_view[local(origin)] = true;
+
+        let edge = (-radius..=radius).flat_map(|i| {
+            vec![
+                Coordinate::new(i, -radius),
+                Coordinate::new(i, radius),
+                Coordinate::new(-radius, i),
+                Coordinate::new(radius, i),
+            ]
+        });
+        for target in edge {
+            let mut line = Line::new(origin.into(), (origin + target).into());
+            while let Some(p) = line.step() {
+                let p = Coordinate::from(p);
+                let offset = p - origin;
+                if !self.extent.is_inside(p)
+                    || offset.x * offset.x + offset.y * offset.y > radius * radius
+                {
+                    break;
+                }
+                in_view[local(p)] = true;
+                if self.blocks_light(p) {
+                    break;
+                }
+            }
+        }
+
+        (0..side * side)
+            .filter(|&i| in_view[i as usize])
+            .map(|i| origin + Coordinate::new(i % side, i / side) - radius)
+            .collect()
+    }
+
+    /// Works out what `faction` can see now, given where its creatures are and how far each of
+    /// them can see.
+    pub fn update_visibility(&mut self, faction: i32, viewers: &[(Coordinate, i32)]) {
+        let in_view: Vec<_> = viewers
+            .iter()
+            .flat_map(|&(p, radius)| self.field_of_view(p, radius))
+            .collect();
+
+        let extent = self.extent;
+        let visibility = self
+            .visibility
+            .entry(faction)
+            .or_insert_with(|| FactionVisibility::new(extent));
+        visibility.hide_all();
+        for p in in_view {
+            visibility.reveal(p);
+        }
+    }
+
+    /// What `faction` could see the last time its visibility was updated.
+    pub fn visibility(&self, faction: i32) -> Option<&FactionVisibility> {
+        self.visibility.get(&faction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::nature_objects::NatureObjectPresets;
+    use crate::game::game_data::map::tests::grass_map;
+
+    #[test]
+    fn trees_block_sight_but_can_themselves_be_seen() {
+        let (mut map, _) = grass_map(Size::new(21, 18), 22);
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let oak = presets.index_of("oak tree").unwrap();
+        // A row of trees across the middle of the map
+        for x in 0..21 {
+            map.create_nature_object(Coordinate::new(x, 10), oak, &presets);
+        }
+
+        let viewer = Coordinate::new(10, 5);
+        assert!(map.line_of_sight(viewer, Coordinate::new(10, 10)));
+        assert!(!map.line_of_sight(viewer, Coordinate::new(10, 15)));
+
+        let in_view = map.field_of_view(viewer, 8);
+        assert!(in_view.contains(&viewer));
+        assert!(in_view.contains(&Coordinate::new(4, 5)));
+        assert!(in_view.contains(&Coordinate::new(10, 10)));
+        assert!(!in_view.contains(&Coordinate::new(10, 11)));
+        assert!(!in_view.contains(&Coordinate::new(10, 14)));
+
+        map.update_visibility(0, &[(viewer, 8)]);
+        map.update_visibility(0, &[(Coordinate::new(10, 15), 3)]);
+        let visibility = map.visibility(0).unwrap();
+        assert!(visibility.is_visible(Coordinate::new(10, 14)));
+        assert!(!visibility.is_visible(viewer));
+        assert!(visibility.has_seen(viewer));
+        assert!(map.visibility(1).is_none());
+    }
+
+    #[test]
+    fn a_negative_radius_sees_nothing() {
+        let (map, _) = grass_map(Size::new(11, 6), 25);
+        let viewer = Coordinate::new(2, 3);
+
+        assert_eq!(map.field_of_view(viewer, 0), vec![viewer]);
+        assert!(map.field_of_view(viewer, -2).is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::nature_objects::NatureObjectPresets;
+use crate::data::random::Generator;
+use crate::game::game_data::map::{Map, NatureObject, TileFlags};
+
+impl Map {
+    /// Water freezes at or below this temperature, and ice thaws above it.
+    const FREEZING_TEMPERATURE: i32 = 0;
+    /// Water this shallow freezes, as does water that has stopped flowing, however deep it is.
+    const FREEZING_WATER_DEPTH: i32 = 20;
+    /// The chance, one in this many, that a piece of water freezes or a piece of ice thaws each
+    /// tick, so that the map freezes over and thaws gradually.
+    const ICE_CHANGE_CHANCE: i32 = 250;
+
+    /// Lets water freeze into ice while the weather is cold, and ice thaw back into water once it
+    /// warms up.
+    pub fn update_ice(&mut self, presets: &NatureObjectPresets, generator: &mut dyn Generator) {
+        if self.weather.temperature() <= Self::FREEZING_TEMPERATURE {
+            let ice = match presets.index_of("ice") {
+                Some(ice) => ice,
+                None => return,
+            };
+            let freezing: Vec<_> = self
+                .water_list
+                .iter()
+                .filter(|(_, water)| {
+                    water.depth() <= Self::FREEZING_WATER_DEPTH || water.is_inert()
+                })
+                .map(|(_, water)| water.position)
+                .collect();
+            for p in freezing {
+                if generator.generate_integer_up_to(Self::ICE_CHANGE_CHANCE - 1) == 0 {
+                    self.freeze_water(p, ice, presets);
+                }
+            }
+        } else {
+            let mut thawing: Vec<_> = self
+                .nature_list
+                .values()
+                .filter(|n| n.is_ice())
+                .map(NatureObject::uid)
+                .collect();
+            // The nature list is a hash map, so its order has to be fixed to keep the same seed
+            // thawing the same ice
+            thawing.sort_unstable();
+            for uid in thawing {
+                if generator.generate_integer_up_to(Self::ICE_CHANGE_CHANCE - 1) == 0 {
+                    self.thaw_ice(uid);
+                }
+            }
+        }
+    }
+
+    /// Turns the water at `p` into an ice object made from the preset `ice`, which can be walked
+    /// over but keeps other water out. Returns whether the water froze.
+    pub fn freeze_water(
+        &mut self,
+        p: Coordinate,
+        ice: usize,
+        presets: &NatureObjectPresets,
+    ) -> bool {
+        if self.water(p).is_none() || self.tile_map.nature_object(p) >= 0 {
+            return false;
+        }
+        let flags = self.tile_map.flags(p);
+        let uid = match self.create_nature_object(p, ice, presets) {
+            Some(uid) => uid,
+            None => return false,
+        };
+
+        if let Some(mut water) = self.remove_water(p) {
+            water.set_inert(false);
+            if let Some(frozen) = self.nature_list.get_mut(uid) {
+                frozen.set_frozen_water(water, flags);
+            }
+        }
+        self.tile_map.set_walkable(p, true);
+        self.tile_map.set_blocks_water(p, true);
+
+        true
+    }
+
+    /// Melts the ice object with the given uid, putting back the water it was frozen from.
+    /// Returns whether there was ice to thaw.
+    pub fn thaw_ice(&mut self, uid: isize) -> bool {
+        if !self
+            .nature_list
+            .get(&uid)
+            .map_or(false, NatureObject::is_ice)
+        {
+            return false;
+        }
+        let ice = match self.remove_nature_object(uid) {
+            Some(ice) => ice,
+            None => return false,
+        };
+
+        let p = ice.pos();
+        if let Some(flags) = ice.frozen_flags() {
+            for &flag in &[
+                TileFlags::VISIBLE,
+                TileFlags::WALKABLE,
+                TileFlags::BUILDABLE,
+                TileFlags::BLOCKS_WATER,
+            ] {
+                self.tile_map.set_flag(p, flag, flags.contains(flag));
+            }
+        }
+        if let Some(&water) = ice.frozen_water() {
+            self.add_water(p, water);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::water_node::WaterNode;
+
+    #[test]
+    fn frozen_rivers_can_be_crossed_and_thaw_back_into_water() {
+        let mut generator = DefaultGenerator::default_with_seed(19);
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let ice = presets.index_of("ice").unwrap();
+        let mut map = Map::new(Size::new(12, 7));
+        let river = Coordinate::new(5, 3);
+        map.add_water(
+            river,
+            WaterNode::new(river, WaterNode::RIVER_DEPTH, 0, &mut generator),
+        );
+        map.tile_map.set_buildable(river, false);
+        let flags = map.tile_map.flags(river);
+        map.update_cache();
+        assert!(!map.cache_tile(river).unwrap().is_walkable());
+
+        assert!(map.freeze_water(river, ice, &presets));
+        map.update_cache();
+        assert!(map.water(river).is_none());
+        assert!(map.nature_object(river).unwrap().is_ice());
+        assert!(map.cache_tile(river).unwrap().is_walkable());
+
+        let uid = map.nature_object(river).unwrap().uid();
+        assert!(map.thaw_ice(uid));
+        map.update_cache();
+        assert!(map.nature_object(river).is_none());
+        assert_eq!(map.water(river).unwrap().depth(), WaterNode::RIVER_DEPTH);
+        assert_eq!(map.water(river).unwrap().time_from_river_bed(), 0);
+        assert_eq!(map.tile_map.flags(river), flags);
+        assert!(!map.cache_tile(river).unwrap().is_walkable());
+    }
+}
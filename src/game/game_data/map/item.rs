@@ -18,19 +18,37 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::coordinate::Coordinate;
+use crate::game::game_data::entity::Entity;
 use crate::game::game_data::map::MapGraphicDrawable;
 use tcod::Color;
 
-pub struct Item {}
+pub struct Item {
+    entity: Entity,
+    graphic: char,
+    color: Color,
+}
+
+impl Item {
+    pub fn new(uid: isize, pos: Coordinate, name: &str, graphic: char, color: Color) -> Self {
+        Self {
+            entity: Entity::new(pos, uid, name.to_string()),
+            graphic,
+            color,
+        }
+    }
 
-impl Item {}
+    pub fn pos(&self) -> Coordinate {
+        self.entity.pos()
+    }
+}
 
 impl MapGraphicDrawable for Item {
     fn graphic(&self) -> char {
-        unimplemented!()
+        self.graphic
     }
 
     fn fore_color(&self) -> Color {
-        unimplemented!()
+        self.color
     }
 }
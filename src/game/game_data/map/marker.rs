@@ -18,14 +18,23 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::coordinate::Coordinate;
-use crate::game::game_data::map::MapGraphicDrawable;
-use tcod::Color;
+use crate::coordinate::{Coordinate, Direction};
+use crate::game::game_data::map::{Map, MapGraphicDrawable};
+use crate::util::tcod::Chars;
+use std::f32::consts::PI;
+use tcod::{colors, Color};
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum MarkerType {
+    /// Flashes between its color and white.
     Flashing,
+    /// Lights up the tile it is on in its color, without changing.
+    Highlight,
+    /// Fades in and out, quicker than a flashing marker.
+    Pulsing,
 }
 
+#[derive(Debug, Clone)]
 pub struct MapMarker {
     marker_type: MarkerType,
     color: Color,
@@ -34,28 +43,77 @@ pub struct MapMarker {
     graphic: char,
     pub pos: Coordinate,
     counter: f32,
-    /*
-
-    class MapMarker {
-        GC_SERIALIZABLE_CLASS
-
-        MarkerType type;
-        TCODColor origColor, color;
-        int duration;
-        int graphic;
-        int x, y; //TODO switch to Coordinate
-        float counter;
-    public:
-        MapMarker(MarkerType=FLASHINGMARKER, int graphic='?', Coordinate position=Coordinate(0,0),
-            int duration=1, TCODColor color=TCODColor::pink);
-        bool Update();
-        int X() const;
-        int Y() const;
-        Coordinate Position() const;
-        int Graphic() const;
-        TCODColor Color() const;
-    };
-        */
+}
+
+impl MapMarker {
+    /// The duration of a marker that stays until it is removed.
+    pub const PERMANENT: i32 = -1;
+
+    /// Creates a marker showing `graphic` at `pos` for `duration` ticks, or until it is removed if
+    /// `duration` is [`MapMarker::PERMANENT`].
+    pub fn new(
+        marker_type: MarkerType,
+        graphic: char,
+        pos: Coordinate,
+        duration: i32,
+        color: Color,
+    ) -> Self {
+        Self {
+            marker_type,
+            color,
+            original_color: color,
+            duration,
+            graphic,
+            pos,
+            counter: 0.,
+        }
+    }
+
+    /// Creates a pulsing arrow at `pos` pointing towards `direction`, to point the player at
+    /// something next to it.
+    pub fn arrow(pos: Coordinate, direction: Direction, duration: i32, color: Color) -> Self {
+        let graphic = match direction {
+            Direction::North | Direction::NorthEast | Direction::NorthWest => Chars::ArrowN,
+            Direction::South | Direction::SouthEast | Direction::SouthWest => Chars::ArrowS,
+            Direction::East => Chars::ArrowE,
+            Direction::West => Chars::ArrowW,
+            Direction::None => Chars::DArrowV,
+        };
+        Self::new(MarkerType::Pulsing, graphic.into(), pos, duration, color)
+    }
+
+    pub fn marker_type(&self) -> MarkerType {
+        self.marker_type
+    }
+
+    /// Moves the marker's animation along by one tick and counts down its duration. Returns
+    /// whether the marker is still to be shown.
+    ///
+    /// Update in original
+    pub fn update(&mut self) -> bool {
+        if self.duration > 0 {
+            self.duration -= 1;
+        }
+
+        match self.marker_type {
+            MarkerType::Flashing => {
+                self.color =
+                    colors::lerp(self.original_color, colors::WHITE, self.counter.sin().abs());
+                self.counter += 0.1;
+            }
+            MarkerType::Highlight => {}
+            MarkerType::Pulsing => {
+                self.color =
+                    colors::lerp(colors::BLACK, self.original_color, self.counter.sin().abs());
+                self.counter += 0.25;
+            }
+        }
+        if self.counter > 2. * PI {
+            self.counter = 0.;
+        }
+
+        self.duration != 0
+    }
 }
 
 impl MapGraphicDrawable for MapMarker {
@@ -64,6 +122,99 @@ impl MapGraphicDrawable for MapMarker {
     }
 
     fn fore_color(&self) -> Color {
-        self.color
+        if self.marker_type == MarkerType::Highlight {
+            colors::BLACK
+        } else {
+            self.color
+        }
+    }
+
+    fn back_color(&self) -> Color {
+        if self.marker_type == MarkerType::Highlight {
+            self.color
+        } else {
+            colors::BLACK
+        }
+    }
+}
+
+impl Map {
+    /// Puts `marker` on the map, returning the id it can be removed by.
+    ///
+    /// AddMarker in original
+    pub fn add_marker(&mut self, marker: MapMarker) -> i32 {
+        let id = self.marker_ids;
+        self.marker_ids += 1;
+        self.map_markers.push((id, marker));
+
+        id
+    }
+
+    pub fn remove_marker(&mut self, id: i32) -> Option<MapMarker> {
+        let index = self.map_markers.iter().position(|&(i, _)| i == id)?;
+        Some(self.map_markers.remove(index).1)
+    }
+
+    pub fn marker(&self, id: i32) -> Option<&MapMarker> {
+        self.map_markers
+            .iter()
+            .find(|&&(i, _)| i == id)
+            .map(|(_, marker)| marker)
+    }
+
+    /// Every marker on the map, along with its id.
+    pub fn markers(&self) -> impl Iterator<Item = (i32, &MapMarker)> {
+        self.map_markers.iter().map(|(id, marker)| (*id, marker))
+    }
+
+    /// Animates the markers for one tick, removing those that have run out.
+    ///
+    /// UpdateMarkers in original
+    pub fn update_markers(&mut self) {
+        let mut expired = vec![];
+        for (id, marker) in &mut self.map_markers {
+            if !marker.update() {
+                expired.push(*id);
+            }
+        }
+        self.map_markers.retain(|(id, _)| !expired.contains(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+
+    #[test]
+    fn markers_expire_unless_permanent() {
+        let mut map = Map::new(Size::new(10, 10));
+        let p = Coordinate::new(5, 5);
+        let brief = map.add_marker(MapMarker::new(
+            MarkerType::Flashing,
+            '!',
+            p,
+            2,
+            colors::PINK,
+        ));
+        let permanent = map.add_marker(MapMarker::arrow(
+            p,
+            Direction::North,
+            MapMarker::PERMANENT,
+            colors::RED,
+        ));
+        assert_ne!(brief, permanent);
+
+        map.update_markers();
+        assert!(map.marker(brief).is_some());
+        map.update_markers();
+        assert!(map.marker(brief).is_none());
+
+        for _ in 0..100 {
+            map.update_markers();
+        }
+        assert_eq!(map.markers().count(), 1);
+        assert!(map.remove_marker(permanent).is_some());
+        assert!(map.remove_marker(permanent).is_none());
     }
 }
@@ -18,14 +18,18 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::coordinate::Coordinate;
+use crate::data::nature_objects::NatureObjectPreset;
 use crate::game::game_data::entity::Entity;
-use crate::game::game_data::map::MapGraphicDrawable;
+use crate::game::game_data::map::{MapGraphicDrawable, TileFlags};
+use crate::game::game_data::water_node::WaterNode;
 use tcod::{colors, Color};
 
 pub struct NatureObject {
     entity: Entity,
 
-    nature_object_type: i32,
+    /// The index of this object's preset in the nature object table.
+    preset: usize,
     graphic: char,
     color: Color,
     marked: bool,
@@ -33,7 +37,79 @@ pub struct NatureObject {
     tree: bool,
     harvestable: bool,
     ice: bool,
-    // static std::vector<NatureObjectPreset> Presets;
+    /// The water this ice was frozen from, which comes back when it thaws.
+    frozen_water: Option<WaterNode>,
+    /// The flags of the tile before the water on it froze, which are put back when it thaws.
+    frozen_flags: Option<TileFlags>,
+}
+
+impl NatureObject {
+    pub fn new(
+        uid: isize,
+        pos: Coordinate,
+        preset_index: usize,
+        preset: &NatureObjectPreset,
+    ) -> Self {
+        Self {
+            entity: Entity::new(pos, uid, preset.name.clone()),
+            preset: preset_index,
+            graphic: preset.graphic(),
+            color: preset.color(),
+            marked: false,
+            condition: preset.condition,
+            tree: preset.tree,
+            harvestable: preset.harvestable,
+            ice: preset.ice,
+            frozen_water: None,
+            frozen_flags: None,
+        }
+    }
+
+    pub fn uid(&self) -> isize {
+        self.entity.uid()
+    }
+
+    pub fn pos(&self) -> Coordinate {
+        self.entity.pos()
+    }
+
+    pub fn name(&self) -> &str {
+        self.entity.name()
+    }
+
+    pub fn preset(&self) -> usize {
+        self.preset
+    }
+
+    /// How much wear, such as chopping or harvesting, the object can take before it is gone.
+    pub fn condition(&self) -> i32 {
+        self.condition
+    }
+
+    pub fn is_tree(&self) -> bool {
+        self.tree
+    }
+
+    pub fn is_harvestable(&self) -> bool {
+        self.harvestable
+    }
+
+    pub fn is_ice(&self) -> bool {
+        self.ice
+    }
+
+    pub fn frozen_water(&self) -> Option<&WaterNode> {
+        self.frozen_water.as_ref()
+    }
+
+    pub fn frozen_flags(&self) -> Option<TileFlags> {
+        self.frozen_flags
+    }
+
+    pub fn set_frozen_water(&mut self, water: WaterNode, flags: TileFlags) {
+        self.frozen_water = Some(water);
+        self.frozen_flags = Some(flags);
+    }
 }
 
 impl MapGraphicDrawable for NatureObject {
@@ -53,3 +129,118 @@ impl MapGraphicDrawable for NatureObject {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::coordinate::Coordinate;
+    use crate::data::base::Size;
+    use crate::data::nature_objects::NatureObjectPresets;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::map::tests::grass_map;
+    use crate::game::game_data::map::{Map, TileType};
+    use itertools::iproduct;
+
+    /// Whether every nature object on the map is referenced by the tile it stands on, and every
+    /// tile only references a nature object that exists.
+    fn in_sync(map: &Map) -> bool {
+        map.nature_list
+            .values()
+            .all(|n| map.tile_map.nature_object(n.pos()) == n.uid())
+            && iproduct!(0..map.extent.height, 0..map.extent.width).all(|(y, x)| {
+                let uid = map.tile_map.nature_object(Coordinate::new(x, y));
+                uid < 0 || map.nature_list.contains_key(&uid)
+            })
+    }
+
+    #[test]
+    fn creating_and_removing_nature_objects_keeps_the_tiles_in_sync() {
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let bush = presets.index_of("bush").unwrap();
+        let mut map = Map::new(Size::new(6, 4));
+        let p = Coordinate::new(3, 2);
+
+        let uid = map.create_nature_object(p, bush, &presets).unwrap();
+        assert_eq!(map.nature_object(p).unwrap().uid(), uid);
+        assert_eq!(map.create_nature_object(p, bush, &presets), None);
+        assert!(in_sync(&map));
+
+        assert_eq!(map.remove_nature_object(uid).unwrap().pos(), p);
+        assert!(map.nature_object(p).is_none());
+        assert!(map.remove_nature_object(uid).is_none());
+        assert!(in_sync(&map));
+    }
+
+    #[test]
+    fn removing_a_nature_object_gives_the_tile_back_what_its_type_allows() {
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let oak = presets.index_of("oak tree").unwrap();
+        let (mut map, _) = grass_map(Size::new(5, 3), 7);
+        let grass = Coordinate::new(1, 1);
+        let nothing = Coordinate::new(0, 0);
+        map.tile_map.reset_type_and_height(
+            nothing,
+            TileType::None,
+            0.,
+            &mut DefaultGenerator::default_with_seed(7),
+        );
+
+        let uid = map.create_nature_object(grass, oak, &presets).unwrap();
+        assert!(!map.tile_map.is_walkable(grass));
+        map.remove_nature_object(uid);
+        assert!(map.tile_map.is_walkable(grass));
+
+        let uid = map.create_nature_object(nothing, oak, &presets).unwrap();
+        map.remove_nature_object(uid);
+        assert!(!map.tile_map.is_walkable(nothing));
+    }
+
+    #[test]
+    fn nature_objects_only_grow_where_there_is_no_construction() {
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let bush = presets.index_of("bush").unwrap();
+        let (mut map, _) = grass_map(Size::new(4, 6), 7);
+        let p = Coordinate::new(2, 3);
+        map.tile_map.set_construction(p, 0);
+
+        assert_eq!(map.create_nature_object(p, bush, &presets), None);
+        assert!(in_sync(&map));
+    }
+
+    #[test]
+    fn naturifying_grass_grows_plants_on_it() {
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let (mut map, mut generator) = grass_map(Size::new(24, 18), 7);
+
+        for _ in 0..40 {
+            for (y, x) in iproduct!(0..18, 0..24) {
+                map.naturify(Coordinate::new(x, y), &presets, &mut generator);
+            }
+        }
+
+        assert!(!map.nature_list.is_empty());
+        assert!(in_sync(&map));
+    }
+
+    #[test]
+    fn naturifying_only_marks_tiles_it_changes() {
+        let mut generator = DefaultGenerator::default_with_seed(21);
+        let presets = NatureObjectPresets::load_built_in().unwrap();
+        let bush = presets.index_of("bush").unwrap();
+        let mut map = Map::new(Size::new(5, 9));
+        let overgrown = Coordinate::new(1, 6);
+        let trail = Coordinate::new(3, 2);
+        map.create_nature_object(overgrown, bush, &presets).unwrap();
+        map.tile_map.set_walked_over(trail, 2);
+        map.dirty_chunks.drain_changed_tiles().for_each(drop);
+
+        map.naturify(overgrown, &presets, &mut generator);
+        assert_eq!(map.dirty_chunks.drain_changed_tiles().count(), 0);
+
+        map.naturify(trail, &presets, &mut generator);
+        assert_eq!(map.tile_map.walked_over(trail), 1);
+        assert_eq!(
+            map.dirty_chunks.drain_changed_tiles().collect::<Vec<_>>(),
+            vec![trail]
+        );
+    }
+}
@@ -18,17 +18,41 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::coordinate::Coordinate;
+use crate::game::game_data::entity::Entity;
 use crate::game::game_data::map::MapGraphicDrawable;
 use tcod::Color;
 
-pub struct Npc {}
+pub struct Npc {
+    entity: Entity,
+    graphic: char,
+    color: Color,
+}
+
+impl Npc {
+    pub fn new(uid: isize, pos: Coordinate, name: &str, graphic: char, color: Color) -> Self {
+        Self {
+            entity: Entity::new(pos, uid, name.to_string()),
+            graphic,
+            color,
+        }
+    }
+
+    pub fn pos(&self) -> Coordinate {
+        self.entity.pos()
+    }
+
+    pub fn set_pos(&mut self, pos: Coordinate) {
+        self.entity.set_pos(pos);
+    }
+}
 
 impl MapGraphicDrawable for Npc {
     fn graphic(&self) -> char {
-        unimplemented!()
+        self.graphic
     }
 
     fn fore_color(&self) -> Color {
-        unimplemented!()
+        self.color
     }
 }
@@ -0,0 +1,374 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::base::Size;
+use crate::game::game_data::map::{CacheTile, Map, MapExtentHelper};
+use crate::game::game_data::GameData;
+use crate::util::extras::Array2DCoordinateAccessor;
+use crate::util::Array2D;
+use bitflags::bitflags;
+use itertools::iproduct;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+bitflags! {
+    /// How a creature gets around, which decides where its paths can take it.
+    #[derive(Default)]
+    pub struct Movement: u8 {
+        /// Can swim across water too deep to wade through.
+        const SWIMS = 0b0000_0001;
+        /// Flies over water and fire, though not over walls.
+        const FLIES = 0b0000_0010;
+        /// Won't go through fire at all, rather than only preferring not to.
+        const AVOIDS_FIRE = 0b0000_0100;
+        /// Can open doors and walk through them.
+        const OPENS_DOORS = 0b0000_1000;
+    }
+}
+
+/// Identifies a path requested with [`Map::request_path`], for collecting it once it is found.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PathTicket(u32);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PathResult {
+    /// The steps to take to reach the goal, ending with the goal itself.
+    Found(Vec<Coordinate>),
+    /// There is no way to the goal, or it was too far away to find one.
+    Unreachable,
+}
+
+/// A path being searched for with A*, which can be left off and picked up again on a later tick.
+struct Search {
+    ticket: PathTicket,
+    goal: Coordinate,
+    movement: Movement,
+    /// The tiles still to be looked at, cheapest estimated total cost first.
+    open: BinaryHeap<Reverse<(i32, Coordinate)>>,
+    /// The cheapest known cost of getting to each tile found so far.
+    costs: HashMap<Coordinate, i32>,
+    came_from: HashMap<Coordinate, Coordinate>,
+    expanded: usize,
+}
+
+impl Search {
+    fn new(ticket: PathTicket, start: Coordinate, goal: Coordinate, movement: Movement) -> Self {
+        let mut search = Self {
+            ticket,
+            goal,
+            movement,
+            open: BinaryHeap::new(),
+            costs: HashMap::new(),
+            came_from: HashMap::new(),
+            expanded: 0,
+        };
+        search.costs.insert(start, 0);
+        search.open.push(Reverse((search.estimate(start), start)));
+
+        search
+    }
+
+    /// Every step costs at least 1, and diagonal steps cost the same as straight ones, so this
+    /// never overestimates the cost of getting to the goal.
+    fn estimate(&self, p: Coordinate) -> i32 {
+        (p.x - self.goal.x).abs().max((p.y - self.goal.y).abs())
+    }
+
+    /// Looks at up to `budget` more tiles, using up as much of the budget as it needed. Returns
+    /// the result once the search is over.
+    fn run(
+        &mut self,
+        tiles: &Array2D<CacheTile>,
+        extent: Size,
+        budget: &mut usize,
+    ) -> Option<PathResult> {
+        if !extent.is_inside(self.goal)
+            || tiles
+                .by_coordinate(self.goal)
+                .step_cost(self.movement)
+                .is_none()
+        {
+            return Some(PathResult::Unreachable);
+        }
+
+        while *budget > 0 {
+            let (estimated, current) = match self.open.pop() {
+                Some(Reverse(node)) => node,
+                None => return Some(PathResult::Unreachable),
+            };
+            let cost = self.costs[&current];
+            // A cheaper way to the tile was found after this entry was queued, so the tile is
+            // looked at from that one instead
+            if estimated > cost + self.estimate(current) {
+                continue;
+            }
+            if current == self.goal {
+                return Some(PathResult::Found(self.path_to(current)));
+            }
+            if self.expanded >= PathFinder::MAX_TILES_PER_SEARCH {
+                return Some(PathResult::Unreachable);
+            }
+            *budget -= 1;
+            self.expanded += 1;
+
+            for (dy, dx) in iproduct!(-1..=1, -1..=1) {
+                let next = current + Coordinate::new(dx, dy);
+                if next == current || !extent.is_inside(next) {
+                    continue;
+                }
+                let step = match tiles.by_coordinate(next).step_cost(self.movement) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                let next_cost = cost + step;
+                if self.costs.get(&next).map_or(true, |&c| next_cost < c) {
+                    self.costs.insert(next, next_cost);
+                    self.came_from.insert(next, current);
+                    self.open
+                        .push(Reverse((next_cost + self.estimate(next), next)));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn path_to(&self, goal: Coordinate) -> Vec<Coordinate> {
+        let mut path = vec![goal];
+        let mut current = goal;
+        while let Some(&previous) = self.came_from.get(&current) {
+            path.push(previous);
+            current = previous;
+        }
+        // The start is where the creature already is, so it isn't a step to take
+        path.pop();
+        path.reverse();
+
+        path
+    }
+}
+
+/// Finds paths across the map in the order they were requested, only looking at so many tiles
+/// each tick so that a crowd asking for paths at once doesn't hold up the game.
+pub struct PathFinder {
+    searches: VecDeque<Search>,
+    /// The paths found but not collected yet, along with how many ticks they have waited.
+    results: HashMap<PathTicket, (PathResult, i32)>,
+    next_ticket: u32,
+}
+
+impl PathFinder {
+    /// How many tiles all the searches together may look at each tick.
+    pub const TILES_PER_TICK: usize = 20_000;
+    /// A search gives up after looking at this many tiles, treating the goal as unreachable.
+    pub const MAX_TILES_PER_SEARCH: usize = 200_000;
+    /// A path that hasn't been collected this many ticks after it was found is thrown away, so
+    /// that paths nobody collects don't pile up.
+    pub const RESULT_TICKS: i32 = GameData::UPDATES_PER_SECOND * 60;
+
+    pub fn new() -> Self {
+        Self {
+            searches: VecDeque::new(),
+            results: HashMap::new(),
+            next_ticket: 0,
+        }
+    }
+
+    fn new_ticket(&mut self) -> PathTicket {
+        let ticket = PathTicket(self.next_ticket);
+        self.next_ticket = self.next_ticket.wrapping_add(1);
+        ticket
+    }
+
+    fn request(&mut self, start: Coordinate, goal: Coordinate, movement: Movement) -> PathTicket {
+        let ticket = self.new_ticket();
+        self.searches
+            .push_back(Search::new(ticket, start, goal, movement));
+
+        ticket
+    }
+
+    /// Hands out a ticket for a path that is already known not to exist.
+    fn reject(&mut self) -> PathTicket {
+        let ticket = self.new_ticket();
+        self.results.insert(ticket, (PathResult::Unreachable, 0));
+
+        ticket
+    }
+
+    fn cancel(&mut self, ticket: PathTicket) {
+        self.searches.retain(|s| s.ticket != ticket);
+        self.results.remove(&ticket);
+    }
+
+    /// Works through the queued searches until this tick's budget runs out. A search that isn't
+    /// done by then carries on from where it was on the next tick.
+    fn update(&mut self, tiles: &Array2D<CacheTile>, extent: Size) {
+        self.results.retain(|_, (_, age)| {
+            *age += 1;
+            *age < Self::RESULT_TICKS
+        });
+
+        let mut budget = Self::TILES_PER_TICK;
+        while budget > 0 {
+            let search = match self.searches.front_mut() {
+                Some(search) => search,
+                None => break,
+            };
+            match search.run(tiles, extent, &mut budget) {
+                Some(result) => {
+                    let ticket = search.ticket;
+                    self.searches.pop_front();
+                    self.results.insert(ticket, (result, 0));
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl Map {
+    /// Asks for a path from `start` to `goal` for a creature getting around by `movement`. The
+    /// path is searched for over the next few ticks, and collected with [`Map::take_path`].
+    pub fn request_path(
+        &mut self,
+        start: Coordinate,
+        goal: Coordinate,
+        movement: Movement,
+    ) -> PathTicket {
+        if self.is_walled_off(start, goal, movement) {
+            self.path_finder.reject()
+        } else {
+            self.path_finder.request(start, goal, movement)
+        }
+    }
+
+    /// Collects the path asked for with `ticket`, or returns `None` if it hasn't been found yet.
+    /// A path has to be collected within [`PathFinder::RESULT_TICKS`] ticks of being found, after
+    /// which it is gone.
+    pub fn take_path(&mut self, ticket: PathTicket) -> Option<PathResult> {
+        self.path_finder
+            .results
+            .remove(&ticket)
+            .map(|(result, _)| result)
+    }
+
+    /// Stops searching for the path asked for with `ticket`, or throws it away if it was found.
+    pub fn cancel_path(&mut self, ticket: PathTicket) {
+        self.path_finder.cancel(ticket);
+    }
+
+    /// Whether walking from `start` to `goal` is impossible, which can be told without searching
+    /// for a path. Swimmers and flyers can get past what keeps walkers apart, so this only
+    /// rules out paths for those who walk.
+    fn is_walled_off(&self, start: Coordinate, goal: Coordinate, movement: Movement) -> bool {
+        !movement.intersects(Movement::SWIMS | Movement::FLIES)
+            && self.zones.zone(start).is_some()
+            && !self.zones.reachable(start, goal)
+    }
+
+    /// Searches for the requested paths for one tick.
+    pub fn update_paths(&mut self) {
+        self.path_finder.update(&self.cached_tile_map, self.extent);
+    }
+
+    /// Finds a path from `start` to `goal` straight away, however long it takes.
+    pub fn find_path(&self, start: Coordinate, goal: Coordinate, movement: Movement) -> PathResult {
+        if self.is_walled_off(start, goal, movement) {
+            return PathResult::Unreachable;
+        }
+        let mut search = Search::new(PathTicket(u32::max_value()), start, goal, movement);
+        let mut budget = PathFinder::MAX_TILES_PER_SEARCH + 1;
+        search
+            .run(&self.cached_tile_map, self.extent, &mut budget)
+            .unwrap_or(PathResult::Unreachable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_data::map::tests::grass_map;
+    use crate::game::game_data::water_node::WaterNode;
+
+    #[test]
+    fn paths_go_around_deep_water_unless_swimming() {
+        let (mut map, mut generator) = grass_map(Size::new(23, 17), 23);
+        // A river across the map with a ford at the bottom
+        for y in 0..16 {
+            let p = Coordinate::new(10, y);
+            map.add_water(
+                p,
+                WaterNode::new(p, WaterNode::RIVER_DEPTH, 0, &mut generator),
+            );
+        }
+        map.update_cache();
+
+        let start = Coordinate::new(5, 2);
+        let goal = Coordinate::new(15, 2);
+        let walked = match map.find_path(start, goal, Movement::empty()) {
+            PathResult::Found(path) => path,
+            PathResult::Unreachable => panic!("the ford should be found"),
+        };
+        assert_eq!(walked.last(), Some(&goal));
+        assert!(walked.contains(&Coordinate::new(10, 16)));
+
+        let ticket = map.request_path(start, goal, Movement::SWIMS);
+        assert_eq!(map.take_path(ticket), None);
+        map.update_paths();
+        match map.take_path(ticket) {
+            Some(PathResult::Found(swum)) => assert_eq!(swum.len(), 10),
+            result => panic!("expected a path, got {:?}", result),
+        }
+
+        let off_map = map.request_path(start, Coordinate::new(30, 30), Movement::SWIMS);
+        map.update_paths();
+        assert_eq!(map.take_path(off_map), Some(PathResult::Unreachable));
+    }
+
+    #[test]
+    fn paths_nobody_collects_are_thrown_away() {
+        let (mut map, _) = grass_map(Size::new(8, 3), 31);
+        map.update_cache();
+
+        let collected = map.request_path(
+            Coordinate::new(0, 0),
+            Coordinate::new(7, 2),
+            Movement::FLIES,
+        );
+        let forgotten = map.request_path(
+            Coordinate::new(7, 0),
+            Coordinate::new(0, 1),
+            Movement::FLIES,
+        );
+        for _ in 0..PathFinder::RESULT_TICKS {
+            map.update_paths();
+        }
+        assert!(matches!(
+            map.take_path(collected),
+            Some(PathResult::Found(_))
+        ));
+
+        map.update_paths();
+        assert_eq!(map.take_path(forgotten), None);
+        assert!(map.path_finder.results.is_empty());
+    }
+}
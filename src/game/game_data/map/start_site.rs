@@ -0,0 +1,276 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::{Coordinate, Direction};
+use crate::data::random::Generator;
+use crate::game::game_data::map::{Map, MapExtentHelper, TileType};
+use tcod::Color;
+
+/// The creatures a camp starts out with: how many there are of each, what they are called, and how
+/// they are drawn.
+const STARTING_NPCS: [(u32, &str, char, (u8, u8, u8)); 2] = [
+    (15, "Goblin", 'g', (0, 200, 0)),
+    (6, "Orc", 'o', (0, 150, 50)),
+];
+
+/// The supplies a camp starts out with, scattered around the start area.
+const STARTING_ITEMS: [(u32, &str, char, (u8, u8, u8)); 4] = [
+    (30, "Bloodberry seed", ',', (200, 0, 0)),
+    (5, "Blueleaf seed", ',', (0, 100, 255)),
+    (30, "Nightbloom seed", ',', (150, 0, 200)),
+    (20, "Bread", '%', (200, 150, 80)),
+];
+
+/// What each of the two dead woodsmen near the camp left behind, along with their corpse.
+const WOODSMAN_ITEMS: [(&str, char, (u8, u8, u8)); 2] = [
+    ("Stone axe", '/', (150, 150, 150)),
+    ("Shovel", '/', (130, 90, 40)),
+];
+
+impl Map {
+    /// How far out from a starting site to look for rivers and hills.
+    const START_SITE_SEARCH_DISTANCE: i32 = 200;
+    /// Rivers closer than this to a starting site count as being very far away, so the camp
+    /// doesn't end up on the river bank.
+    const START_SITE_MIN_RIVER_DISTANCE: i32 = 25;
+    /// The distance used when no river or hill was found at all.
+    const START_SITE_NOT_FOUND_DISTANCE: i32 = 1000;
+    const START_SITE_TOO_CLOSE_DISTANCE: i32 = 2000;
+    const START_SITE_NOT_GRASS_PENALTY: i32 = 10000;
+
+    /// Scores how good a place `candidate` is to start a camp. Higher is better.
+    ///
+    /// The best sites are on grass, near both a river and some hills, but not right next to the
+    /// river.
+    pub fn score_start_site(&self, candidate: Coordinate) -> i32 {
+        let mut river_distance = Self::START_SITE_NOT_FOUND_DISTANCE;
+        let mut hill_distance = Self::START_SITE_NOT_FOUND_DISTANCE;
+
+        // We look along four lines out from the site for the nearest river and rock tiles
+        for &direction in &[
+            Direction::West,
+            Direction::East,
+            Direction::North,
+            Direction::South,
+        ] {
+            for distance in 0..=Self::START_SITE_SEARCH_DISTANCE {
+                let p = candidate + Coordinate::from(direction) * distance;
+                if !self.extent.is_inside(p) {
+                    continue;
+                }
+
+                match self.tile_map.tile_type(p) {
+                    TileType::Ditch | TileType::Riverbed => {
+                        river_distance = river_distance.min(distance)
+                    }
+                    TileType::Rock => hill_distance = hill_distance.min(distance),
+                    _ => {}
+                }
+            }
+        }
+
+        if river_distance < Self::START_SITE_MIN_RIVER_DISTANCE {
+            river_distance = Self::START_SITE_TOO_CLOSE_DISTANCE;
+        }
+
+        let mut score = -hill_distance - river_distance;
+        if self.tile_type(candidate) != TileType::Grass {
+            score -= Self::START_SITE_NOT_GRASS_PENALTY;
+        }
+
+        score
+    }
+
+    /// Picks the best scoring of `tries` random sites, keeping away from the edges of the map.
+    pub fn find_start_site(&self, tries: u32, generator: &mut dyn Generator) -> Coordinate {
+        let extent = Coordinate::from(self.extent);
+        let margin = (self.extent.width.min(self.extent.height) / 5).min(100);
+
+        (0..tries)
+            .map(|_| {
+                generator.generate_coordinate_within_rectangle(
+                    Coordinate::new(margin, margin),
+                    extent - margin,
+                )
+            })
+            .max_by_key(|&candidate| self.score_start_site(candidate))
+            .unwrap_or(extent / 2)
+    }
+
+    /// Removes about two thirds of the nature objects inside the rectangle from `low` to `high`,
+    /// both inclusive, to make room for a camp.
+    pub fn clear_start_area(
+        &mut self,
+        low: Coordinate,
+        high: Coordinate,
+        generator: &mut dyn Generator,
+    ) {
+        let low = self.extent.shrink(low);
+        let high = self.extent.shrink(high);
+        for x in low.x..=high.x {
+            for y in low.y..=high.y {
+                let uid = self.tile_map.nature_object(Coordinate::new(x, y));
+                if uid >= 0 && generator.generate_integer_up_to(2) < 2 {
+                    self.remove_nature_object(uid);
+                }
+            }
+        }
+    }
+
+    /// Places the camp's first goblins and orcs, its supplies, and the corpses of two woodsmen
+    /// with their tools, all on walkable tiles inside the rectangle from `low` to `high`, both
+    /// inclusive. The goblins and orcs are kept close to the center of the rectangle.
+    pub fn populate_start_area(
+        &mut self,
+        low: Coordinate,
+        high: Coordinate,
+        generator: &mut dyn Generator,
+    ) {
+        const NPC_MARGIN: i32 = 15;
+        const WOODSMEN: u32 = 2;
+
+        for &(count, name, graphic, (r, g, b)) in &STARTING_NPCS {
+            for _ in 0..count {
+                if let Some(p) =
+                    self.find_walkable_tile(low + NPC_MARGIN, high - NPC_MARGIN, generator)
+                {
+                    self.create_npc(p, name, graphic, Color::new(r, g, b));
+                }
+            }
+        }
+
+        for &(count, name, graphic, (r, g, b)) in &STARTING_ITEMS {
+            for _ in 0..count {
+                if let Some(p) = self.find_walkable_tile(low, high, generator) {
+                    self.create_item(p, name, graphic, Color::new(r, g, b));
+                }
+            }
+        }
+
+        for _ in 0..WOODSMEN {
+            if let Some(p) = self.find_walkable_tile(low, high, generator) {
+                for &(name, graphic, (r, g, b)) in &WOODSMAN_ITEMS {
+                    self.create_item(p, name, graphic, Color::new(r, g, b));
+                }
+                self.create_item(p, "Corpse(Human woodsman)", '%', tcod::colors::WHITE);
+                // TODO: Splatter blood around the corpse once blood exists
+            }
+        }
+    }
+
+    /// Picks a random walkable tile inside the rectangle from `low` to `high`, both inclusive,
+    /// giving up after a while if there doesn't seem to be one.
+    fn find_walkable_tile(
+        &self,
+        low: Coordinate,
+        high: Coordinate,
+        generator: &mut dyn Generator,
+    ) -> Option<Coordinate> {
+        const TRIES: u32 = 100;
+
+        let low = self.extent.shrink(low);
+        let high = self.extent.shrink(high);
+        (0..TRIES)
+            .map(|_| generator.generate_coordinate_within_rectangle(low, high))
+            .find(|&p| self.tile_map.is_walkable(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::map::tests::grass_map;
+    use crate::game::game_data::map::MapGraphicDrawable;
+    use itertools::iproduct;
+
+    #[test]
+    fn start_site_prefers_grass_near_river_and_hills() {
+        let mut generator = DefaultGenerator::default_with_seed(8);
+        let mut map = Map::new(Size::new(150, 150));
+        for y in 0..150 {
+            map.set_tile_type(Coordinate::new(40, y), TileType::Riverbed, &mut generator);
+            map.set_tile_type(Coordinate::new(120, y), TileType::Rock, &mut generator);
+        }
+
+        let good = map.score_start_site(Coordinate::new(80, 75));
+        let far_from_hills = map.score_start_site(Coordinate::new(10, 75));
+        let on_river_bank = map.score_start_site(Coordinate::new(45, 75));
+        let on_rock = map.score_start_site(Coordinate::new(120, 75));
+        assert!(good > far_from_hills);
+        assert!(good > on_river_bank);
+        assert!(good > on_rock);
+        assert_eq!(good, -40 - 40);
+    }
+
+    #[test]
+    fn the_start_area_gets_goblins_orcs_supplies_and_two_dead_woodsmen() {
+        let (mut map, mut generator) = grass_map(Size::new(60, 50), 8);
+        let low = Coordinate::new(10, 5);
+        let high = Coordinate::new(50, 45);
+        map.populate_start_area(low, high, &mut generator);
+
+        let count_npcs = |graphic| {
+            map.npc_list
+                .values()
+                .filter(|n| n.graphic() == graphic)
+                .count()
+        };
+        assert_eq!(count_npcs('g'), 15);
+        assert_eq!(count_npcs('o'), 6);
+        assert!(map
+            .npc_list
+            .values()
+            .all(|n| n.pos().inside_rectangle(low + 15, high - 15)));
+
+        let count_items = |graphic| {
+            map.item_list
+                .values()
+                .filter(|i| i.graphic() == graphic)
+                .count()
+        };
+        // The seeds, the bread along with the corpses, and the woodsmen's tools
+        assert_eq!(count_items(','), 65);
+        assert_eq!(count_items('%'), 22);
+        assert_eq!(count_items('/'), 4);
+        assert!(map
+            .item_list
+            .values()
+            .all(|i| i.pos().inside_rectangle(low, high)));
+    }
+
+    #[test]
+    fn nothing_is_placed_where_nobody_can_walk() {
+        let mut generator = DefaultGenerator::default_with_seed(8);
+        let mut map = Map::new(Size::new(40, 40));
+        for (y, x) in iproduct!(0..40, 0..40) {
+            map.set_tile_type(Coordinate::new(x, y), TileType::None, &mut generator);
+        }
+        map.populate_start_area(
+            Coordinate::new(0, 0),
+            Coordinate::new(39, 39),
+            &mut generator,
+        );
+
+        assert!(map.npc_list.is_empty());
+        assert!(map.item_list.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::game::game_data::construction::Tag;
+use crate::game::game_data::map::{Map, MapExtentHelper, TileFlags};
+use itertools::iproduct;
+use std::collections::HashSet;
+
+/// Something the player can order done on a tile. Whether it can be done outside the player's
+/// territory is decided by [`Map::territory_allows`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TerritoryAction {
+    /// Placing a construction, described by the tag that says what kind of construction it is.
+    Build(Tag),
+    /// Designating a stockpile or a farm plot.
+    Designate,
+    Dig,
+    /// Carrying an item lying on the tile to a stockpile.
+    Haul,
+    FellTree,
+    /// Gathering berries, mushrooms and other wild plants.
+    Harvest,
+}
+
+impl TerritoryAction {
+    /// Whether this can only be done inside the player's territory. Gathering what nature provides
+    /// can be done anywhere, but the camp itself only grows inside its territory. The exceptions
+    /// are the constructions that defend the camp or lead away from it, which are often needed
+    /// just beyond its edges.
+    pub fn requires_territory(self) -> bool {
+        match self {
+            Self::Build(Tag::Wall) | Self::Build(Tag::Door) | Self::Build(Tag::Trap) => false,
+            Self::Build(Tag::Bridge) => false,
+            Self::Build(_) | Self::Designate | Self::Dig | Self::Haul => true,
+            Self::FellTree | Self::Harvest => false,
+        }
+    }
+}
+
+impl Map {
+    /// How far the territory reaches out from the edges of a construction when it gets expanded
+    /// around it.
+    const CONSTRUCTION_TERRITORY_MARGIN: i32 = 5;
+
+    pub fn is_territory(&self, p: Coordinate) -> bool {
+        self.extent.is_inside(p) && self.tile_map.is_territory(p)
+    }
+
+    /// Makes `p` part of the player's territory, or takes it out of it if `owned` is `false`.
+    pub fn set_territory(&mut self, p: Coordinate, owned: bool) {
+        if self.extent.is_inside(p) {
+            self.tile_map.set_territory(p, owned);
+            self.dirty_chunks.mark(p);
+        }
+    }
+
+    /// Makes every tile in the rectangle from `low` to `high`, both inclusive, part of the
+    /// player's territory, or takes them out of it if `owned` is `false`.
+    pub fn set_territory_rectangle(&mut self, low: Coordinate, high: Coordinate, owned: bool) {
+        let low = self.extent.shrink(low);
+        let high = self.extent.shrink(high);
+        for x in low.x..=high.x {
+            for y in low.y..=high.y {
+                self.set_territory(Coordinate::new(x, y), owned);
+            }
+        }
+    }
+
+    /// Like [`Map::set_territory_rectangle`], but for an area of any shape. Tiles outside the map
+    /// are ignored.
+    pub fn set_territory_area<I>(&mut self, area: I, owned: bool)
+    where
+        I: IntoIterator<Item = Coordinate>,
+    {
+        for p in area {
+            self.set_territory(p, owned);
+        }
+    }
+
+    /// Adds the area around the construction with the given uid, which has a tile at `p`, to the
+    /// player's territory. Only the tiles of the construction itself are looked at to find its
+    /// extent. Returns `false` if the tile at `p` doesn't hold that construction.
+    pub fn expand_territory_around_construction(&mut self, uid: isize, p: Coordinate) -> bool {
+        if !self.extent.is_inside(p) || self.tile_map.construction(p) != uid {
+            return false;
+        }
+
+        let (mut low, mut high) = (p, p);
+        let mut visited = HashSet::new();
+        let mut pending = vec![p];
+        visited.insert(p);
+        while let Some(p) = pending.pop() {
+            low = low.min(p);
+            high = high.max(p);
+            for &d in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let n = p + Coordinate::from(d);
+                if self.extent.is_inside(n)
+                    && self.tile_map.construction(n) == uid
+                    && visited.insert(n)
+                {
+                    pending.push(n);
+                }
+            }
+        }
+
+        self.set_territory_rectangle(
+            low - Self::CONSTRUCTION_TERRITORY_MARGIN,
+            high + Self::CONSTRUCTION_TERRITORY_MARGIN,
+            true,
+        );
+        true
+    }
+
+    /// How many tiles are part of the player's territory.
+    pub fn territory_size(&self) -> usize {
+        self.territory_tiles().count()
+    }
+
+    /// The tiles of the player's territory that are next to a tile outside of it. The edges of the
+    /// map don't count as being outside.
+    pub fn territory_border(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        self.territory_tiles().filter(move |&p| {
+            [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .map(|&d| p + Coordinate::from(d))
+                .any(|n| self.extent.is_inside(n) && !self.tile_map.is_territory(n))
+        })
+    }
+
+    /// Whether `action` may be done at `p`, given the player's territory.
+    pub fn territory_allows(&self, p: Coordinate, action: TerritoryAction) -> bool {
+        self.extent.is_inside(p) && (!action.requires_territory() || self.is_territory(p))
+    }
+
+    /// Whether `action` may be done on every tile of the rectangle from `low` to `high`, both
+    /// inclusive, such as the footprint of a construction.
+    pub fn territory_allows_rectangle(
+        &self,
+        low: Coordinate,
+        high: Coordinate,
+        action: TerritoryAction,
+    ) -> bool {
+        iproduct!(low.x..=high.x, low.y..=high.y)
+            .all(|(x, y)| self.territory_allows(Coordinate::new(x, y), action))
+    }
+
+    fn territory_tiles(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        let tile_map = &self.tile_map;
+        tile_map
+            .all_flags()
+            .iter()
+            .enumerate()
+            .filter(|&(_, flags)| flags.contains(TileFlags::TERRITORY))
+            .map(move |(index, _)| tile_map.coordinate(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+
+    #[test]
+    fn territory_rules_and_queries() {
+        let mut map = Map::new(Size::new(150, 150));
+        map.set_territory_rectangle(Coordinate::new(10, 10), Coordinate::new(19, 19), true);
+        assert_eq!(map.territory_size(), 100);
+        assert_eq!(map.territory_border().count(), 36);
+
+        let inside = Coordinate::new(15, 15);
+        let outside = Coordinate::new(50, 50);
+        assert!(map.territory_allows(inside, TerritoryAction::Build(Tag::Workshop)));
+        assert!(!map.territory_allows(outside, TerritoryAction::Build(Tag::Workshop)));
+        assert!(map.territory_allows(outside, TerritoryAction::FellTree));
+        assert!(!map.territory_allows_rectangle(
+            Coordinate::new(18, 18),
+            Coordinate::new(20, 20),
+            TerritoryAction::Designate,
+        ));
+
+        map.tile_map.set_construction(outside, 7);
+        assert!(map.expand_territory_around_construction(7, outside));
+        assert!(map.territory_allows(outside + 5, TerritoryAction::Haul));
+        assert!(!map.territory_allows(outside + 6, TerritoryAction::Haul));
+        assert!(!map.expand_territory_around_construction(8, outside));
+        assert!(!map.expand_territory_around_construction(7, outside + 1));
+    }
+
+    #[test]
+    fn defenses_and_bridges_can_be_built_outside_the_territory() {
+        let map = Map::new(Size::new(20, 20));
+        let outside = Coordinate::new(5, 5);
+
+        for &tag in &[Tag::Wall, Tag::Door, Tag::Trap, Tag::Bridge] {
+            assert!(map.territory_allows(outside, TerritoryAction::Build(tag)));
+        }
+        for &tag in &[Tag::Workshop, Tag::Bed, Tag::SpawningPool] {
+            assert!(!map.territory_allows(outside, TerritoryAction::Build(tag)));
+        }
+    }
+
+    #[test]
+    fn the_whole_footprint_of_a_construction_gets_territory_around_it() {
+        let mut map = Map::new(Size::new(40, 40));
+        for x in 10..=12 {
+            for y in 20..=21 {
+                map.tile_map.set_construction(Coordinate::new(x, y), 3);
+            }
+        }
+
+        assert!(map.expand_territory_around_construction(3, Coordinate::new(11, 21)));
+        assert_eq!(map.territory_size(), 13 * 12);
+        assert!(map.is_territory(Coordinate::new(5, 15)));
+        assert!(map.is_territory(Coordinate::new(17, 26)));
+        assert!(!map.is_territory(Coordinate::new(18, 26)));
+    }
+}
@@ -18,83 +18,499 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-#![allow(clippy::default_trait_access)]
-
-use crate::coordinate::Direction;
+use crate::coordinate::{Coordinate, Direction};
+use crate::data::base::Size;
 use crate::data::random::Generator;
 use crate::game::game_data::construction::{Construction, Tag};
 use crate::game::game_data::filth_node::FilthNode;
-use crate::game::game_data::map::MapGraphicDrawable;
+use crate::game::game_data::map::{Deposit, FireNode, MapExtentHelper, Movement};
 use crate::game::game_data::water_node::WaterNode;
-use derivative::Derivative;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::util::{SafeConsole, SlotHandle};
+use bitflags::bitflags;
+use serde_derive::{Deserialize, Serialize};
 use tcod::Color;
 
-#[derive(Clone, Derivative)]
-#[derivative(Default)]
-pub struct Tile {
-    tile_type: TileType,
-    vis: bool,
-    walkable: bool,
-    buildable: bool,
-    move_cost: i32,
-    pub construction: isize,
-    low: bool,
-    blocks_water: bool,
-    pub water: Option<Rc<RefCell<WaterNode>>>,
-    graphic: char,
-    fore_color: Color,
-    original_fore_color: Color,
-    back_color: Color,
-    #[derivative(Default(value = "-1"))]
-    pub nature_object_ref: isize,
+bitflags! {
+    /// The yes-or-no properties of a tile.
+    #[derive(Default)]
+    pub struct TileFlags: u8 {
+        const VISIBLE = 0b0000_0001;
+        const WALKABLE = 0b0000_0010;
+        const BUILDABLE = 0b0000_0100;
+        /// Ditches, riverbeds and mud lie lower than the surrounding land.
+        const LOW = 0b0000_1000;
+        const BLOCKS_WATER = 0b0001_0000;
+        const MARKED = 0b0010_0000;
+        /// The tile is part of the player's territory.
+        const TERRITORY = 0b0100_0000;
+    }
+}
+
+/// Every tile of a map. Instead of storing each tile as one struct, each property of the tiles is
+/// stored as its own contiguous layer, so that a pass over the whole map only has to go through
+/// the layers it actually uses.
+///
+/// The layers are stored row by row, and a tile's place in them is given by [`TileMap::index`].
+/// Every method that looks a tile up by its coordinate goes through it, and so panics if the
+/// coordinate is outside the map; callers check against the map's extent first.
+pub struct TileMap {
+    extent: Size,
+    tile_type: Vec<TileType>,
+    flags: Vec<TileFlags>,
+    move_cost: Vec<i32>,
+    construction: Vec<isize>,
+    nature_object: Vec<isize>,
+    /// The water on each tile, which is kept in the map's list of water nodes.
+    water: Vec<Option<SlotHandle<WaterNode>>>,
+    /// The filth on each tile, which is kept in the map's list of filth nodes.
+    filth: Vec<Option<SlotHandle<FilthNode>>>,
+    /// The mineral held by each tile, if it is rock with a deposit in it.
+    deposit: Vec<Option<Deposit>>,
+    /// The type of the tile hidden under the snow on each snow covered tile.
+    covered_type: Vec<Option<TileType>>,
     //std::set<int> npcList; //Set of NPC uid's
     //std::set<int> itemList; //Set of Item uid's
-    pub filth: Option<FilthNode>,
-    //boost::shared_ptr<FilthNode> filth;
     //boost::shared_ptr<BloodNode> blood;
-    //boost::shared_ptr<FireNode> fire;
-    marked: bool,
-    pub walked_over: i32,
-    pub corruption: i32,
-    pub territory: bool,
-    pub burnt: i32,
-    pub flow: Direction,
+    fire: Vec<Option<SlotHandle<FireNode>>>,
+    graphic: Vec<char>,
+    fore_color: Vec<Color>,
+    original_fore_color: Vec<Color>,
+    back_color: Vec<Color>,
+    walked_over: Vec<i32>,
+    corruption: Vec<i32>,
+    burnt: Vec<i32>,
+    flow: Vec<Direction>,
 }
 
-impl Tile {
+impl TileMap {
+    /// The temperature below which grass starts to wither.
+    pub const GRASS_WITHERING_TEMPERATURE: i32 = 10;
+    /// Tiles with at least this much corruption count as corrupted, and only evil plants grow on
+    /// them.
+    pub const CORRUPTED: i32 = 100;
+    /// The most corruption a tile can hold.
+    pub const MAX_CORRUPTION: i32 = 300;
+    /// How much it costs to walk across plain ground, such as grass and rock.
+    pub const BASE_MOVE_COST: i32 = 2;
+    /// Tiles walked over at least this much have a trail worn into them, which makes them a
+    /// little quicker to walk across.
+    pub const TRAIL: i32 = 100;
+    /// Grass walked over at least this much can be worn away into mud.
+    pub const WORN_TO_MUD: i32 = 300;
+    /// Walking stops wearing down a tile once it has been walked over this much.
+    pub const MAX_WALKED_OVER: i32 = 500;
+
+    pub fn new(extent: Size) -> Self {
+        let area = extent.area() as usize;
+        Self {
+            extent,
+            tile_type: vec![TileType::default(); area],
+            // New tiles are grass, so they start out with the flags grass gets when reset
+            flags: vec![TileFlags::VISIBLE | TileFlags::WALKABLE | TileFlags::BUILDABLE; area],
+            move_cost: vec![Self::BASE_MOVE_COST; area],
+            construction: vec![-1; area],
+            nature_object: vec![-1; area],
+            water: vec![None; area],
+            filth: vec![None; area],
+            deposit: vec![None; area],
+            covered_type: vec![None; area],
+            fire: vec![None; area],
+            graphic: vec!['\0'; area],
+            fore_color: vec![Color::default(); area],
+            original_fore_color: vec![Color::default(); area],
+            back_color: vec![Color::default(); area],
+            walked_over: vec![0; area],
+            corruption: vec![0; area],
+            burnt: vec![0; area],
+            flow: vec![Direction::default(); area],
+        }
+    }
+
+    /// Where the tile at `p` is stored in each layer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is outside the map. Since the layers are stored row by row, a coordinate just
+    /// past the end of a row would otherwise quietly land on the first tile of the next row.
+    pub fn index(&self, p: Coordinate) -> usize {
+        assert!(
+            self.extent.is_inside(p),
+            "{:?} is outside the {} tile map",
+            p,
+            self.extent
+        );
+        (p.y * self.extent.width + p.x) as usize
+    }
+
+    /// The coordinate of the tile stored at `index` in each layer.
+    pub fn coordinate(&self, index: usize) -> Coordinate {
+        let index = index as i32;
+        Coordinate::new(index % self.extent.width, index / self.extent.width)
+    }
+
+    pub fn tile_type(&self, p: Coordinate) -> TileType {
+        self.tile_type[self.index(p)]
+    }
+
+    pub fn tile_types(&self) -> &[TileType] {
+        &self.tile_type
+    }
+
+    pub fn flags(&self, p: Coordinate) -> TileFlags {
+        self.flags[self.index(p)]
+    }
+
+    pub fn set_flag(&mut self, p: Coordinate, flag: TileFlags, value: bool) {
+        let i = self.index(p);
+        self.flags[i].set(flag, value);
+    }
+
+    pub fn all_flags(&self) -> &[TileFlags] {
+        &self.flags
+    }
+
+    pub fn is_walkable(&self, p: Coordinate) -> bool {
+        self.flags(p).contains(TileFlags::WALKABLE)
+    }
+
+    pub fn set_walkable(&mut self, p: Coordinate, walkable: bool) {
+        self.set_flag(p, TileFlags::WALKABLE, walkable);
+    }
+
+    pub fn set_buildable(&mut self, p: Coordinate, buildable: bool) {
+        self.set_flag(p, TileFlags::BUILDABLE, buildable);
+    }
+
+    pub fn set_blocks_water(&mut self, p: Coordinate, blocks_water: bool) {
+        self.set_flag(p, TileFlags::BLOCKS_WATER, blocks_water);
+    }
+
+    /// Whether light, and with it sight, is stopped by the tile itself or what grows on it.
+    pub fn blocks_light(&self, p: Coordinate) -> bool {
+        !self.flags(p).contains(TileFlags::VISIBLE)
+    }
+
+    pub fn set_blocks_light(&mut self, p: Coordinate, blocks_light: bool) {
+        self.set_flag(p, TileFlags::VISIBLE, !blocks_light);
+    }
+
+    pub fn is_territory(&self, p: Coordinate) -> bool {
+        self.flags(p).contains(TileFlags::TERRITORY)
+    }
+
+    pub fn set_territory(&mut self, p: Coordinate, territory: bool) {
+        self.set_flag(p, TileFlags::TERRITORY, territory);
+    }
+
+    /// The uid of the construction standing on `p`, or -1 if there is none.
+    pub fn construction(&self, p: Coordinate) -> isize {
+        self.construction[self.index(p)]
+    }
+
+    pub fn set_construction(&mut self, p: Coordinate, uid: isize) {
+        let i = self.index(p);
+        self.construction[i] = uid;
+    }
+
+    /// The uid of the nature object growing on `p`, or -1 if there is none.
+    pub fn nature_object(&self, p: Coordinate) -> isize {
+        self.nature_object[self.index(p)]
+    }
+
+    pub fn set_nature_object(&mut self, p: Coordinate, uid: isize) {
+        let i = self.index(p);
+        self.nature_object[i] = uid;
+    }
+
+    /// How many nature objects there are in the rectangle from `low` to `high`, both inclusive.
+    pub fn count_nature_objects(&self, low: Coordinate, high: Coordinate) -> i32 {
+        (low.y..=high.y)
+            .map(|y| {
+                let start = self.index(Coordinate::new(low.x, y));
+                let end = self.index(Coordinate::new(high.x, y));
+                self.nature_object[start..=end]
+                    .iter()
+                    .filter(|&&uid| uid >= 0)
+                    .count() as i32
+            })
+            .sum()
+    }
+
+    pub fn water(&self, p: Coordinate) -> Option<SlotHandle<WaterNode>> {
+        self.water[self.index(p)]
+    }
+
+    pub fn set_water(&mut self, p: Coordinate, water: Option<SlotHandle<WaterNode>>) {
+        let i = self.index(p);
+        self.water[i] = water;
+    }
+
+    pub fn has_water(&self, p: Coordinate) -> bool {
+        self.water(p).is_some()
+    }
+
+    pub fn filth(&self, p: Coordinate) -> Option<SlotHandle<FilthNode>> {
+        self.filth[self.index(p)]
+    }
+
+    pub fn set_filth(&mut self, p: Coordinate, filth: Option<SlotHandle<FilthNode>>) {
+        let i = self.index(p);
+        self.filth[i] = filth;
+    }
+
+    pub fn has_filth(&self, p: Coordinate) -> bool {
+        self.filth(p).is_some()
+    }
+
+    pub fn fire(&self, p: Coordinate) -> Option<SlotHandle<FireNode>> {
+        self.fire[self.index(p)]
+    }
+
+    pub fn set_fire(&mut self, p: Coordinate, fire: Option<SlotHandle<FireNode>>) {
+        let i = self.index(p);
+        self.fire[i] = fire;
+    }
+
+    pub fn deposit(&self, p: Coordinate) -> Option<Deposit> {
+        self.deposit[self.index(p)]
+    }
+
+    pub fn deposit_mut(&mut self, p: Coordinate) -> &mut Option<Deposit> {
+        let i = self.index(p);
+        &mut self.deposit[i]
+    }
+
+    pub fn deposits(&self) -> &[Option<Deposit>] {
+        &self.deposit
+    }
+
+    pub fn walked_over(&self, p: Coordinate) -> i32 {
+        self.walked_over[self.index(p)]
+    }
+
+    pub fn set_walked_over(&mut self, p: Coordinate, walked_over: i32) {
+        let i = self.index(p);
+        self.walked_over[i] = walked_over;
+        // Wear shows on grass, so its color needs working out again
+        self.corrupt(p, 0);
+    }
+
+    /// Wears down the tile at `p` from being walked over. Grass gets trampled bare and, once it
+    /// is worn down enough, can turn into mud, unless a construction stands on it.
+    ///
+    /// WalkOver in original
+    pub fn walk_over(&mut self, p: Coordinate, tile_height: f32, generator: &mut dyn Generator) {
+        let i = self.index(p);
+        if self.walked_over[i] < Self::MAX_WALKED_OVER {
+            self.set_walked_over(p, self.walked_over[i] + 1);
+        }
+        if self.tile_type[i] != TileType::Grass {
+            return;
+        }
+
+        if self.walked_over[i] >= Self::TRAIL && self.graphic[i] != '.' && self.graphic[i] != ',' {
+            self.graphic[i] = if generator.generate_bool() { '.' } else { ',' };
+        }
+        if self.walked_over[i] >= Self::WORN_TO_MUD
+            && self.construction[i] < 0
+            && generator.generate_integer_up_to(99) == 0
+        {
+            // Whatever stands on the tile still stands there, but like all mud it now lies low,
+            // so water flows into it
+            let flags = self.flags[i];
+            self.reset_type_and_height(p, TileType::Mud, tile_height, generator);
+            self.flags[i] = flags | TileFlags::LOW;
+        }
+    }
+
+    /// Lets the tile at `p` recover a little from being walked over. Mud grows back into grass
+    /// once nobody has walked over it for long enough.
+    pub fn recover_from_walking(
+        &mut self,
+        p: Coordinate,
+        tile_height: f32,
+        generator: &mut dyn Generator,
+    ) {
+        let i = self.index(p);
+        if self.walked_over[i] == 0 {
+            return;
+        }
+        self.set_walked_over(p, self.walked_over[i] - 1);
+
+        if self.walked_over[i] == 0
+            && self.tile_type[i] == TileType::Mud
+            && self.construction[i] < 0
+        {
+            // Whatever stands on the tile still stands there, but it no longer lies low
+            let flags = self.flags[i];
+            self.reset_type_and_height(p, TileType::Grass, tile_height, generator);
+            self.flags[i] = flags - TileFlags::LOW;
+        }
+    }
+
+    /// How much it costs to walk across the tile at `p`. Trails are a little cheaper to walk
+    /// along than the ground around them.
+    pub fn move_cost(&self, p: Coordinate) -> i32 {
+        let i = self.index(p);
+        if self.walked_over[i] >= Self::TRAIL {
+            (self.move_cost[i] - 1).max(1)
+        } else {
+            self.move_cost[i]
+        }
+    }
+
+    pub fn corruption(&self, p: Coordinate) -> i32 {
+        self.corruption[self.index(p)]
+    }
+
+    pub fn is_corrupted(&self, p: Coordinate) -> bool {
+        self.corruption(p) >= Self::CORRUPTED
+    }
+
+    pub fn burnt(&self, p: Coordinate) -> i32 {
+        self.burnt[self.index(p)]
+    }
+
+    pub fn flow(&self, p: Coordinate) -> Direction {
+        self.flow[self.index(p)]
+    }
+
+    pub fn set_flow(&mut self, p: Coordinate, flow: Direction) {
+        let i = self.index(p);
+        self.flow[i] = flow;
+    }
+
+    pub fn flows_mut(&mut self) -> &mut [Direction] {
+        &mut self.flow
+    }
+
+    pub fn fore_color(&self, p: Coordinate) -> Color {
+        self.fore_color[self.index(p)]
+    }
+
+    /// Draws the tile at `p` onto `console` at `at`.
+    pub fn draw(&self, p: Coordinate, console: &mut dyn SafeConsole, at: Coordinate) {
+        let i = self.index(p);
+        console.put_char_ex(
+            at.into(),
+            self.graphic[i],
+            self.fore_color[i],
+            self.back_color[i],
+        );
+    }
+
+    fn grass_color(tile_height: f32, generator: &mut dyn Generator) -> Color {
+        let mut color = Color::new(generator.generate_up_to_u8(49), 127, 0);
+        if generator.generate_integer_up_to(9) < 9 {
+            if tile_height < -0.01 {
+                color = Color::new(generator.generate_u8(100, 192), 127, 0);
+            } else if tile_height < 0.0 {
+                color = Color::new(generator.generate_u8(20, 170), 127, 0);
+            } else if tile_height > 4.0 {
+                color = Color::new(90, generator.generate_u8(120, 150), 90);
+            }
+        }
+
+        color
+    }
+
+    /// Gives the grass at `p` a new color to suit the temperature. Below
+    /// [`TileMap::GRASS_WITHERING_TEMPERATURE`], the colder it is, the more yellowed and brown the
+    /// grass gets.
+    pub fn recolor_grass(
+        &mut self,
+        p: Coordinate,
+        tile_height: f32,
+        temperature: i32,
+        generator: &mut dyn Generator,
+    ) {
+        let i = self.index(p);
+        if self.tile_type[i] != TileType::Grass {
+            return;
+        }
+
+        let mut color = Self::grass_color(tile_height, generator);
+        let chill = (Self::GRASS_WITHERING_TEMPERATURE - temperature)
+            .max(0)
+            .min(20);
+        color.r = (i32::from(color.r) + chill * 6).min(200) as u8;
+        color.g = (i32::from(color.g) - chill * 2).max(0) as u8;
+        self.original_fore_color[i] = color;
+        // Corrupting by 0 works out the final color, with any wear, corruption and burns
+        self.corrupt(p, 0);
+    }
+
+    /// Whether the tile at `p` is covered by snow that can melt away again.
+    pub fn is_snow_covered(&self, p: Coordinate) -> bool {
+        self.covered_type[self.index(p)].is_some()
+    }
+
+    /// Covers the tile at `p` with snow, remembering its type so it can be restored when the
+    /// snow melts. What stands on the tile and what lies in it are left as they were.
+    pub fn cover_with_snow(
+        &mut self,
+        p: Coordinate,
+        tile_height: f32,
+        generator: &mut dyn Generator,
+    ) {
+        if self.is_snow_covered(p) {
+            return;
+        }
+
+        let i = self.index(p);
+        let (tile_type, flags, deposit) = (self.tile_type[i], self.flags[i], self.deposit[i]);
+        self.reset_type_and_height(p, TileType::Snow, tile_height, generator);
+        self.flags[i] = flags;
+        self.deposit[i] = deposit;
+        self.covered_type[i] = Some(tile_type);
+    }
+
+    /// Melts the snow on the tile at `p`, turning it back into the type it was before it was
+    /// covered. Returns that type, if there was any snow to melt.
+    pub fn melt_snow(
+        &mut self,
+        p: Coordinate,
+        tile_height: f32,
+        generator: &mut dyn Generator,
+    ) -> Option<TileType> {
+        let i = self.index(p);
+        let tile_type = self.covered_type[i]?;
+
+        let (flags, deposit) = (self.flags[i], self.deposit[i]);
+        self.reset_type_and_height(p, tile_type, tile_height, generator);
+        self.flags[i] = flags;
+        self.deposit[i] = deposit;
+        // Bring back any wear, corruption and burns
+        self.corrupt(p, 0);
+
+        Some(tile_type)
+    }
+
     pub fn reset_type_and_height(
         &mut self,
+        p: Coordinate,
         tile_type: TileType,
         tile_height: f32,
         generator: &mut dyn Generator,
     ) {
         // TODO: Do some magic number extractions up in here
 
-        self.tile_type = tile_type;
-        self.vis = true;
-        self.walkable = true;
-        self.buildable = true;
-        self.low = false;
+        let i = self.index(p);
+        self.tile_type[i] = tile_type;
+        self.covered_type[i] = None;
+        if tile_type != TileType::Rock {
+            self.deposit[i] = None;
+        }
+        self.flags[i].insert(TileFlags::VISIBLE);
+        self.reset_flags(p);
+        self.flags[i].remove(TileFlags::LOW);
 
-        match self.tile_type {
+        match tile_type {
             TileType::Grass => {
-                self.original_fore_color = Color::new(generator.generate_up_to_u8(49), 127, 0);
-                if generator.generate_integer_up_to(9) < 9 {
-                    if tile_height < -0.01 {
-                        self.original_fore_color =
-                            Color::new(generator.generate_u8(100, 192), 127, 0);
-                    } else if tile_height < 0.0 {
-                        self.original_fore_color =
-                            Color::new(generator.generate_u8(20, 170), 127, 0);
-                    } else if tile_height > 4.0 {
-                        self.original_fore_color =
-                            Color::new(90, generator.generate_u8(120, 150), 90);
-                    }
-                }
-                self.back_color = Color::new(0, 0, 0);
-                self.graphic = match generator.generate_integer_up_to(9) {
+                self.move_cost[i] = Self::BASE_MOVE_COST;
+                self.original_fore_color[i] = Self::grass_color(tile_height, generator);
+                self.back_color[i] = Color::new(0, 0, 0);
+                self.graphic[i] = match generator.generate_integer_up_to(9) {
                     0..=3 => '.',
                     4..=7 => ',',
                     8 => ':',
@@ -102,51 +518,53 @@ impl Tile {
                 }
             }
             TileType::Ditch | TileType::Riverbed => {
-                self.low = true;
-                self.graphic = '_';
-                self.original_fore_color = Color::new(125, 50, 0);
-                self.move_cost = generator.generate_integer(3, 5);
-                self.flow = Direction::None; // Reset flow
+                self.flags[i].insert(TileFlags::LOW);
+                self.graphic[i] = '_';
+                self.original_fore_color[i] = Color::new(125, 50, 0);
+                self.move_cost[i] = generator.generate_integer(3, 5);
+                self.flow[i] = Direction::None; // Reset flow
             }
             TileType::Bog => {
-                self.graphic = match generator.generate_integer_up_to(9) {
+                self.graphic[i] = match generator.generate_integer_up_to(9) {
                     0..=3 => '~',
                     4..=7 => ',',
                     8 => ':',
                     _ => '\'',
                 };
-                self.original_fore_color = Color::new(generator.generate_up_to_u8(184), 127, 70);
-                self.back_color = Color::new(60, 30, 20);
-                self.move_cost = generator.generate_integer(6, 10);
+                self.original_fore_color[i] = Color::new(generator.generate_up_to_u8(184), 127, 70);
+                self.back_color[i] = Color::new(60, 30, 20);
+                self.move_cost[i] = generator.generate_integer(6, 10);
             }
             TileType::Rock => {
-                self.graphic = if generator.generate_bool() { ',' } else { '.' };
-                self.original_fore_color = Color::new(
+                self.move_cost[i] = Self::BASE_MOVE_COST;
+                self.graphic[i] = if generator.generate_bool() { ',' } else { '.' };
+                self.original_fore_color[i] = Color::new(
                     generator.generate_u8(182, 201),
                     generator.generate_u8(182, 201),
                     generator.generate_u8(182, 201),
                 );
-                self.back_color = Color::new(0, 0, 0);
+                self.back_color[i] = Color::new(0, 0, 0);
             }
             TileType::Mud => {
-                self.low = true;
-                self.graphic = if generator.generate_bool() { '#' } else { '~' };
-                self.original_fore_color = Color::new(
+                self.flags[i].insert(TileFlags::LOW);
+                self.graphic[i] = if generator.generate_bool() { '#' } else { '~' };
+                self.original_fore_color[i] = Color::new(
                     generator.generate_u8(120, 130),
                     generator.generate_u8(80, 90),
                     0,
                 );
-                self.move_cost = 5;
+                self.move_cost[i] = 5;
             }
             TileType::Snow => {
+                self.move_cost[i] = Self::BASE_MOVE_COST;
                 let color_num = generator.generate_integer(195, 250);
-                self.original_fore_color = Color::new(
+                self.original_fore_color[i] = Color::new(
                     (color_num + generator.generate_integer(-5, 5)) as u8,
                     (color_num + generator.generate_integer(-5, 5)) as u8,
                     (color_num + generator.generate_integer(-5, 5)) as u8,
                 );
-                self.back_color = Color::new(0, 0, 0);
-                self.graphic = match generator.generate_integer_up_to(9) {
+                self.back_color[i] = Color::new(0, 0, 0);
+                self.graphic[i] = match generator.generate_integer_up_to(9) {
                     0..=3 => '.',
                     4..=7 => ',',
                     8 => ':',
@@ -154,72 +572,59 @@ impl Tile {
                 };
             }
             TileType::None => {
-                self.vis = false;
-                self.walkable = false;
-                self.buildable = false;
+                self.flags[i].remove(TileFlags::VISIBLE);
             }
         }
-        self.fore_color = self.original_fore_color;
+        self.fore_color[i] = self.original_fore_color[i];
     }
 
-    pub fn burn(&mut self, magnitude: i32) {
-        if self.tile_type == TileType::Grass {
-            self.burnt = 10.min(self.burnt + magnitude).max(0);
-            if self.burnt == 0 {
-                self.corrupt(0); /*Corruption changes the color, and by corrupting by 0 we just return to what color the tile
-                                 would be without any burning */
+    /// Sets whether the tile can be walked on and built on back to what its tile type allows, such
+    /// as once whatever stood on it is gone.
+    pub fn reset_flags(&mut self, p: Coordinate) {
+        let i = self.index(p);
+        let passable = self.tile_type[i] != TileType::None;
+        self.flags[i].set(TileFlags::WALKABLE | TileFlags::BUILDABLE, passable);
+    }
+
+    pub fn burn(&mut self, p: Coordinate, magnitude: i32) {
+        let i = self.index(p);
+        if self.tile_type[i] == TileType::Grass {
+            let burnt = 10.min(self.burnt[i] + magnitude).max(0);
+            self.burnt[i] = burnt;
+            if burnt == 0 {
+                self.corrupt(p, 0); /*Corruption changes the color, and by corrupting by 0 we just return to what color the tile
+                                    would be without any burning */
                 return;
             }
 
-            if self.tile_type == TileType::Grass {
-                if self.burnt < 5 {
-                    self.fore_color.r = (130 + (5 - self.burnt) * 10) as u8;
-                    self.fore_color.g = (80 + (5 - self.burnt) * 5) as u8;
-                    self.fore_color.b = 0;
-                } else {
-                    self.fore_color.r = (50 + (10 - self.burnt) * 12) as u8;
-                    self.fore_color.g = (50 + (10 - self.burnt) * 6) as u8;
-                    self.fore_color.b = ((self.burnt - 5) * 10) as u8;
-                }
+            let fore_color = &mut self.fore_color[i];
+            if burnt < 5 {
+                fore_color.r = (130 + (5 - burnt) * 10) as u8;
+                fore_color.g = (80 + (5 - burnt) * 5) as u8;
+                fore_color.b = 0;
+            } else {
+                fore_color.r = (50 + (10 - burnt) * 12) as u8;
+                fore_color.g = (50 + (10 - burnt) * 6) as u8;
+                fore_color.b = ((burnt - 5) * 10) as u8;
             }
         }
     }
 
-    pub fn corrupt(&mut self, magnitude: i32) {
-        self.corruption += magnitude;
-        if self.corruption < 0 {
-            self.corruption = 0;
-        }
-        if self.tile_type == TileType::Grass {
-            self.fore_color = self.original_fore_color
-                + Color::new(self.walked_over.min(255) as u8, 0, 0)
-                - Color::new(0, self.corruption.min(255) as u8, 0);
-            if self.burnt > 0 {
-                self.burn(0); // To re-do the color
+    pub fn corrupt(&mut self, p: Coordinate, magnitude: i32) {
+        let i = self.index(p);
+        self.corruption[i] = (self.corruption[i] + magnitude).max(0);
+        if self.tile_type[i] == TileType::Grass {
+            self.fore_color[i] = self.original_fore_color[i]
+                + Color::new(self.walked_over[i].min(255) as u8, 0, 0)
+                - Color::new(0, self.corruption[i].min(255) as u8, 0);
+            if self.burnt[i] > 0 {
+                self.burn(p, 0); // To re-do the color
             }
         }
     }
-
-    pub fn has_water(&self) -> bool {
-        self.water.is_some()
-    }
 }
 
-impl MapGraphicDrawable for Tile {
-    fn graphic(&self) -> char {
-        self.graphic
-    }
-
-    fn fore_color(&self) -> Color {
-        self.fore_color
-    }
-
-    fn back_color(&self) -> Color {
-        self.back_color
-    }
-}
-
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum TileType {
     None,
     Grass,
@@ -254,9 +659,25 @@ pub struct CacheTile {
 }
 
 impl CacheTile {
-    pub fn update_from(&mut self, tile: &Tile, construction: Option<&Construction>) {
-        self.walkable = tile.walkable;
-        self.move_cost = tile.move_cost;
+    /// What swimming across deep water adds to the cost of a step.
+    const SWIM_COST: i32 = 10;
+    /// Wading through shallow water costs one more for every this much depth.
+    const WADING_DEPTH_PER_COST: i32 = 5;
+    /// What walking through fire adds to the cost of a step.
+    const FIRE_COST: i32 = 50;
+    /// What each creature already on a tile adds to the cost of stepping onto it.
+    const CROWD_COST: i32 = 2;
+
+    pub fn update_from(
+        &mut self,
+        tiles: &TileMap,
+        p: Coordinate,
+        construction: Option<&Construction>,
+        water: Option<&WaterNode>,
+    ) {
+        let i = tiles.index(p);
+        self.walkable = tiles.flags[i].contains(TileFlags::WALKABLE);
+        self.move_cost = tiles.move_cost(p);
         if let Some(construction) = construction {
             self.construction = true;
             self.door = construction.has_tag(Tag::Door);
@@ -271,14 +692,160 @@ impl CacheTile {
             self.move_speed_modifier = 0;
         }
 
-        self.water_depth = if let Some(water) = &tile.water {
-            water.borrow().depth()
-        } else {
-            0
-        };
+        self.water_depth = water.map_or(0, WaterNode::depth);
+        self.fire = tiles.fire[i].is_some();
 
         // TODO: This stuff
         //self.npc_count = tile.npc_list.size();
-        //self.fire = tile.fire;
+    }
+
+    /// Whether creatures can walk onto the tile. Deep water can't be walked through, but ice
+    /// and bridges over it can.
+    pub fn is_walkable(&self) -> bool {
+        self.walkable && (self.bridge || self.water_depth <= WaterNode::MAX_WALKABLE_DEPTH)
+    }
+
+    /// How much it costs a creature getting around by `movement` to step onto the tile, or `None`
+    /// if it can't. Water, fire and other creatures in the way make a tile costlier, so paths go
+    /// around them when they can.
+    pub fn step_cost(&self, movement: Movement) -> Option<i32> {
+        if !self.walkable || (self.door && !movement.contains(Movement::OPENS_DOORS)) {
+            return None;
+        }
+
+        // Flyers pass over water and fire alike
+        if movement.contains(Movement::FLIES) {
+            return Some(1 + self.npc_count * Self::CROWD_COST);
+        }
+
+        let mut cost = (self.move_cost + self.move_speed_modifier).max(1);
+        if !self.bridge && self.water_depth > WaterNode::MAX_WALKABLE_DEPTH {
+            if !movement.contains(Movement::SWIMS) {
+                return None;
+            }
+            cost += Self::SWIM_COST;
+        } else if !self.bridge {
+            cost += self.water_depth / Self::WADING_DEPTH_PER_COST;
+        }
+        if self.fire {
+            if movement.contains(Movement::AVOIDS_FIRE) {
+                return None;
+            }
+            cost += Self::FIRE_COST;
+        }
+
+        Some(cost + self.npc_count * Self::CROWD_COST)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::random::DefaultGenerator;
+
+    #[test]
+    fn each_tile_has_its_own_place_in_the_layers() {
+        let tiles = TileMap::new(Size::new(7, 3));
+        let mut seen = vec![false; 7 * 3];
+        for y in 0..3 {
+            for x in 0..7 {
+                let p = Coordinate::new(x, y);
+                let index = tiles.index(p);
+                assert!(!seen[index]);
+                seen[index] = true;
+                assert_eq!(tiles.coordinate(index), p);
+            }
+        }
+    }
+
+    #[test]
+    fn new_tiles_can_be_seen_walked_on_and_built_on_like_grass() {
+        let tiles = TileMap::new(Size::new(3, 2));
+        let p = Coordinate::new(2, 1);
+        assert_eq!(tiles.tile_type(p), TileType::Grass);
+        assert_eq!(
+            tiles.flags(p),
+            TileFlags::VISIBLE | TileFlags::WALKABLE | TileFlags::BUILDABLE
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn tiles_past_the_end_of_a_row_are_not_looked_up() {
+        let tiles = TileMap::new(Size::new(7, 3));
+        tiles.tile_type(Coordinate::new(7, 0));
+    }
+
+    #[test]
+    fn changing_a_tile_leaves_its_neighbors_alone() {
+        let mut generator = DefaultGenerator::default_with_seed(9);
+        let mut tiles = TileMap::new(Size::new(5, 4));
+        for y in 0..4 {
+            for x in 0..5 {
+                tiles.reset_type_and_height(
+                    Coordinate::new(x, y),
+                    TileType::Grass,
+                    0.,
+                    &mut generator,
+                );
+            }
+        }
+
+        let p = Coordinate::new(2, 1);
+        tiles.reset_type_and_height(p, TileType::Rock, 0., &mut generator);
+        tiles.set_walkable(p, false);
+        tiles.set_nature_object(p, 3);
+        tiles.set_walked_over(p, 2);
+
+        for y in 0..4 {
+            for x in 0..5 {
+                let q = Coordinate::new(x, y);
+                if q != p {
+                    assert_eq!(tiles.tile_type(q), TileType::Grass);
+                    assert!(tiles.is_walkable(q));
+                    assert_eq!(tiles.nature_object(q), -1);
+                    assert_eq!(tiles.walked_over(q), 0);
+                }
+            }
+        }
+        assert_eq!(tiles.tile_type(p), TileType::Rock);
+        assert_eq!(tiles.nature_object(p), 3);
+        assert_eq!(tiles.walked_over(p), 2);
+    }
+
+    #[test]
+    fn walking_wears_trails_into_grass() {
+        let mut generator = DefaultGenerator::default_with_seed(10);
+        let mut tiles = TileMap::new(Size::new(10, 10));
+        let p = Coordinate::new(5, 5);
+        tiles.reset_type_and_height(p, TileType::Grass, 0., &mut generator);
+        assert_eq!(tiles.move_cost(p), TileMap::BASE_MOVE_COST);
+
+        for _ in 0..TileMap::TRAIL {
+            tiles.walk_over(p, 0., &mut generator);
+        }
+        assert!(tiles.move_cost(p) < TileMap::BASE_MOVE_COST);
+
+        for _ in TileMap::TRAIL..TileMap::MAX_WALKED_OVER {
+            tiles.walk_over(p, 0., &mut generator);
+        }
+        assert_eq!(tiles.tile_type(p), TileType::Mud);
+        assert!(tiles.move_cost(p) < 5);
+    }
+
+    #[test]
+    fn mud_nobody_walks_on_grows_back_into_grass() {
+        let mut generator = DefaultGenerator::default_with_seed(12);
+        let mut tiles = TileMap::new(Size::new(6, 8));
+        let p = Coordinate::new(1, 6);
+        tiles.reset_type_and_height(p, TileType::Mud, 0., &mut generator);
+        tiles.set_walked_over(p, 2);
+
+        tiles.recover_from_walking(p, 0., &mut generator);
+        assert_eq!(tiles.tile_type(p), TileType::Mud);
+        tiles.recover_from_walking(p, 0., &mut generator);
+        assert_eq!(tiles.tile_type(p), TileType::Grass);
+        assert!(!tiles.flags(p).contains(TileFlags::LOW));
+        assert!(tiles.is_walkable(p));
     }
 }
@@ -0,0 +1,213 @@
+/*
+    Copyright 2010-2011 Ilkka Halila
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::random::Generator;
+use crate::game::game_data::map::{Map, MapExtentHelper, TileFlags};
+use crate::game::game_data::water_node::WaterNode;
+use crate::game::game_data::GameData;
+use crate::util::SlotHandle;
+use itertools::iproduct;
+
+impl Map {
+    /// How many ticks water that had nowhere to go rests before trying to spread again.
+    const WATER_REST_TICKS: i32 = GameData::UPDATES_PER_SECOND;
+    /// Water this shallow can evaporate.
+    const SHALLOW_WATER_DEPTH: i32 = 10;
+    /// The chance, one in this many, that resting shallow water loses some depth when it wakes up.
+    const EVAPORATION_CHANCE: i32 = 10;
+
+    /// Lets all the water on the map spread and evaporate for one tick. Water that had nowhere
+    /// to go the last time it was updated is left alone until it has rested.
+    pub fn update_water(&mut self, generator: &mut dyn Generator) {
+        let due: Vec<_> = self
+            .water_list
+            .iter_mut()
+            .filter_map(|(handle, water)| {
+                if water.rest(Self::WATER_REST_TICKS) {
+                    Some(handle)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for handle in due {
+            self.update_water_node(handle, generator);
+        }
+    }
+
+    fn is_low(&self, p: Coordinate) -> bool {
+        self.tile_map.flags(p).contains(TileFlags::LOW)
+    }
+
+    fn blocks_water(&self, p: Coordinate) -> bool {
+        self.tile_map.flags(p).contains(TileFlags::BLOCKS_WATER)
+    }
+
+    fn water_depth(&self, p: Coordinate) -> i32 {
+        self.water(p).map_or(0, WaterNode::depth)
+    }
+
+    /// Sets the depth of the water at `p` and wakes it up, creating new water there if there is
+    /// none yet.
+    fn set_water_depth(
+        &mut self,
+        p: Coordinate,
+        depth: i32,
+        time_from_river_bed: i32,
+        generator: &mut dyn Generator,
+    ) {
+        let updated = self.modify_water(p, |water| {
+            water.set_depth(depth, generator);
+            water.set_inert(false);
+        });
+        if updated.is_none() {
+            self.add_water(p, WaterNode::new(p, depth, time_from_river_bed, generator));
+        }
+    }
+
+    fn update_water_node(&mut self, handle: SlotHandle<WaterNode>, generator: &mut dyn Generator) {
+        let (pos, mut depth, mut filth, time_from_river_bed) = match self.water_list.get(handle) {
+            Some(water) => (
+                water.position,
+                water.depth(),
+                water.filth(),
+                water.time_from_river_bed(),
+            ),
+            None => return,
+        };
+
+        // Rivers and lakes are fed from off the map, so they stay full
+        if time_from_river_bed == 0 && depth < WaterNode::RIVER_DEPTH {
+            depth = WaterNode::RIVER_DEPTH;
+        }
+
+        let neighbors: Vec<_> = iproduct!(pos.x - 1..=pos.x + 1, pos.y - 1..=pos.y + 1)
+            .map(|(x, y)| Coordinate::new(x, y))
+            .filter(|&n| n != pos && self.extent.is_inside(n) && !self.blocks_water(n))
+            .collect();
+
+        // Water on higher ground drains into neighboring ditches before going anywhere else
+        let only_low = !self.is_low(pos) && neighbors.iter().any(|&n| self.is_low(n));
+        let height = self.height_map.get_value(pos.x, pos.y);
+        let targets: Vec<_> = neighbors
+            .iter()
+            .filter(|&&n| !only_low || self.is_low(n))
+            .filter(|&&n| self.is_low(n) || self.height_map.get_value(n.x, n.y) <= height)
+            .map(|&n| (n, self.water_depth(n)))
+            .filter(|&(_, neighbor_depth)| neighbor_depth < depth - 1)
+            .collect();
+        let coastal = targets
+            .iter()
+            .any(|&(_, neighbor_depth)| neighbor_depth == 0);
+
+        // Even the water out between this tile and the shallower neighbors, carrying filth along
+        let mut spread = false;
+        if depth > 1 && !targets.is_empty() {
+            let total: i32 = depth + targets.iter().map(|&(_, d)| d).sum::<i32>();
+            let share = total / (targets.len() as i32 + 1);
+            let filth_share = filth / (targets.len() as i32 + 1);
+            let mut remaining = total;
+            for &(n, neighbor_depth) in &targets {
+                if share > neighbor_depth {
+                    self.set_water_depth(n, share, time_from_river_bed + 1, generator);
+                    if filth_share > 0 {
+                        self.modify_water(n, |water| water.add_filth(filth_share));
+                        filth -= filth_share;
+                    }
+                    remaining -= share;
+                    spread = true;
+                } else {
+                    remaining -= neighbor_depth;
+                }
+            }
+            if spread {
+                depth = remaining;
+            }
+        }
+
+        let mut evaporated = false;
+        if !spread
+            && time_from_river_bed > 0
+            && depth <= Self::SHALLOW_WATER_DEPTH
+            && generator.generate_integer_up_to(Self::EVAPORATION_CHANCE - 1) == 0
+        {
+            depth -= 1;
+            evaporated = true;
+        }
+
+        // Dried up water leaves its filth behind
+        if depth <= 0 {
+            self.remove_water(pos);
+            self.create_filth(pos, filth, generator);
+            return;
+        }
+
+        let water = self.water_list.get_mut(handle).unwrap();
+        water.set_filth(filth);
+        if depth != water.depth() {
+            water.set_depth(depth, generator);
+            self.dirty_chunks.mark(pos);
+        }
+        water.set_coastal(coastal);
+        water.set_inert(!spread && !evaporated);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+
+    #[test]
+    fn water_spreads_evenly_around_blocked_tiles() {
+        let mut generator = DefaultGenerator::default_with_seed(5);
+        let mut map = Map::new(Size::new(21, 17));
+        let center = Coordinate::new(10, 10);
+        let blocked = Coordinate::new(11, 10);
+        map.tile_map.set_blocks_water(blocked, true);
+        map.add_water(center, WaterNode::new(center, 90, 1, &mut generator));
+
+        map.update_water(&mut generator);
+
+        assert!(map.water(blocked).is_none());
+        assert_eq!(map.water_depth(Coordinate::new(9, 9)), 90 / 8);
+        assert_eq!(map.water_depth(center), 90 - 7 * (90 / 8));
+        let total: i32 = map.water_nodes().map(|(_, w)| w.depth()).sum();
+        assert_eq!(total, 90);
+        assert_eq!(map.water_nodes().count(), 8);
+    }
+
+    #[test]
+    fn rivers_stay_full() {
+        let mut generator = DefaultGenerator::default_with_seed(15);
+        let mut map = Map::new(Size::new(9, 12));
+        let source = Coordinate::new(0, 0);
+        map.add_water(source, WaterNode::new(source, 1, 0, &mut generator));
+
+        map.update_water(&mut generator);
+
+        let neighbor_depth = map.water_depth(Coordinate::new(1, 1));
+        assert_eq!(neighbor_depth, WaterNode::RIVER_DEPTH / 4);
+        assert!(map.water(source).unwrap().depth() >= neighbor_depth);
+    }
+}
@@ -18,22 +18,45 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::coordinate::Direction;
+use crate::coordinate::{Coordinate, Direction};
 use crate::data::random::Generator;
+use crate::game::game_data::map::{Map, TileFlags, TileType};
+use crate::game::game_data::water_node::WaterNode;
+use crate::game::game_data::{GameData, Season};
+use std::ops::Range;
 
 pub struct Weather {
     wind_direction: Direction,
     prevailing_wind_direction: Direction,
     current_weather: WeatherType,
+    /// Whether the map is being swept over to change its tiles to suit a new season.
     tile_change: bool,
+    /// Whether the sweep changes every tile, rather than only some of them.
     change_all: bool,
+    /// How many columns of tiles the sweep goes across each tick.
     tile_change_rate: i32,
+    /// The column the sweep has reached.
     change_position: i32,
     current_temperature: i32,
-    current_season: i32,
+    /// The season the weather is following, if the first season has begun.
+    current_season: Option<Season>,
 }
 
 impl Weather {
+    /// The chance, one in this many, that the temperature moves a degree towards that of the
+    /// season each tick.
+    const TEMPERATURE_CHANGE_CHANCE: i32 = GameData::UPDATES_PER_SECOND * 10;
+    /// The chance, one in this many, that the wind shifts each tick.
+    const WIND_SHIFT_CHANCE: i32 = GameData::UPDATES_PER_SECOND * 30;
+    /// How far, in eighths of a turn, the wind strays from the prevailing wind.
+    const MAX_WIND_DRIFT: i32 = 1;
+    /// The chance, one in this many, that it starts raining each tick.
+    const RAIN_START_CHANCE: i32 = GameData::UPDATES_PER_SECOND * 60 * 5;
+    /// The chance, one in this many, that the rain stops each tick.
+    const RAIN_STOP_CHANCE: i32 = GameData::UPDATES_PER_SECOND * 60;
+    /// How long it takes the sweep to go across the map, in ticks.
+    const TILE_CHANGE_TICKS: i32 = GameData::UPDATES_PER_SECOND * 30;
+
     pub fn new() -> Self {
         Self {
             wind_direction: Direction::North,
@@ -43,18 +66,287 @@ impl Weather {
             change_all: false,
             tile_change_rate: 0,
             change_position: 0,
-            current_season: -1,
+            current_season: None,
             current_temperature: 0,
         }
     }
 
+    pub fn wind_direction(&self) -> Direction {
+        self.wind_direction
+    }
+
+    pub fn is_raining(&self) -> bool {
+        match self.current_weather {
+            WeatherType::Rain => true,
+            WeatherType::Normal => false,
+        }
+    }
+
+    pub fn season(&self) -> Option<Season> {
+        self.current_season
+    }
+
+    pub fn temperature(&self) -> i32 {
+        self.current_temperature
+    }
+
     pub fn randomize_wind(&mut self, generator: &mut dyn Generator) {
         self.prevailing_wind_direction = generator.auto_select();
         self.wind_direction = self.prevailing_wind_direction;
     }
+
+    /// Lets the wind shift, the rain come and go and the temperature change for one tick.
+    pub fn update(&mut self, generator: &mut dyn Generator) {
+        if generator.generate_integer_up_to(Self::WIND_SHIFT_CHANCE - 1) == 0 {
+            self.shift_wind(generator);
+        }
+
+        self.current_weather = match self.current_weather {
+            WeatherType::Normal
+                if self.current_temperature > 0
+                    && generator.generate_integer_up_to(Self::RAIN_START_CHANCE - 1) == 0 =>
+            {
+                WeatherType::Rain
+            }
+            WeatherType::Rain
+                if self.current_temperature <= 0
+                    || generator.generate_integer_up_to(Self::RAIN_STOP_CHANCE - 1) == 0 =>
+            {
+                WeatherType::Normal
+            }
+            weather => weather,
+        };
+
+        if let Some(target) = self.current_season.map(Self::season_temperature) {
+            if self.current_temperature != target
+                && generator.generate_integer_up_to(Self::TEMPERATURE_CHANGE_CHANCE - 1) == 0
+            {
+                self.current_temperature += (target - self.current_temperature).signum();
+            }
+        }
+    }
+
+    /// Turns the wind a little, keeping it close to the prevailing wind.
+    fn shift_wind(&mut self, generator: &mut dyn Generator) {
+        let wind = self.wind_direction.turn(generator.generate_integer(-1, 1));
+        let drift = (-Self::MAX_WIND_DRIFT..=Self::MAX_WIND_DRIFT)
+            .any(|steps| self.prevailing_wind_direction.turn(steps) == wind);
+        if drift {
+            self.wind_direction = wind;
+        }
+    }
+
+    /// The temperature the weather drifts towards in `season`.
+    ///
+    /// SeasonToTemperature in original
+    pub fn season_temperature(season: Season) -> i32 {
+        match season {
+            Season::EarlySpring | Season::LateFall => 5,
+            Season::Spring | Season::Fall => 10,
+            Season::LateSpring | Season::EarlyFall => 15,
+            Season::EarlySummer | Season::LateSummer => 20,
+            Season::Summer => 25,
+            Season::EarlyWinter | Season::LateWinter => 0,
+            Season::Winter => -5,
+        }
+    }
+
+    /// Starts following `season`, and begins sweeping over the map to change its tiles to suit
+    /// it. The first and last parts of each season only change some of the tiles, so the map
+    /// changes gradually from one season into the next.
+    ///
+    /// ApplySeasonalEffects in original
+    pub fn apply_season(&mut self, season: Season, map_width: i32) {
+        if self.current_season.is_none() {
+            // The first season begins with its own temperature
+            self.current_temperature = Self::season_temperature(season);
+        }
+        self.current_season = Some(season);
+        self.tile_change = true;
+        self.change_all = season.is_middle();
+        self.change_position = 0;
+        self.tile_change_rate = (map_width / Self::TILE_CHANGE_TICKS).max(1);
+    }
+
+    /// The columns of tiles the seasonal sweep goes across this tick, if it is under way, and
+    /// whether every tile in them should be changed.
+    fn next_tile_change(&mut self, map_width: i32) -> Option<(Range<i32>, bool)> {
+        if !self.tile_change {
+            return None;
+        }
+
+        let start = self.change_position;
+        let end = (start + self.tile_change_rate).min(map_width);
+        self.change_position = end;
+        if end >= map_width {
+            self.tile_change = false;
+        }
+
+        Some((start..end, self.change_all))
+    }
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum WeatherType {
     Normal,
     Rain,
 }
+
+impl Map {
+    /// How many tiles of the map there are for each drop of rain falling on it each tick.
+    const TILES_PER_RAIN_DROP: i32 = 50_000;
+    /// Out of every this many tiles, the seasonal sweep changes about four when it isn't
+    /// changing all of them.
+    const PARTIAL_TILE_CHANGE: i32 = 9;
+    /// The chance, one in this many, that melting snow leaves a puddle behind.
+    const MELT_WATER_CHANCE: i32 = 4;
+    /// The chance, one in this many, that melting snow turns grass to mud.
+    const MELT_MUD_CHANCE: i32 = 20;
+
+    /// Starts the weather following `season`.
+    pub fn set_season(&mut self, season: Season) {
+        self.weather.apply_season(season, self.extent.width);
+    }
+
+    /// Lets the weather change for one tick, making it rain on the map and sweeping the season
+    /// across it.
+    pub fn update_weather(&mut self, generator: &mut dyn Generator) {
+        self.weather.update(generator);
+
+        if self.weather.is_raining() {
+            let drops = (self.extent.area() / Self::TILES_PER_RAIN_DROP).max(1);
+            for _ in 0..drops {
+                let p = generator.generate_coordinate_within_rectangle(
+                    Coordinate::ORIGIN,
+                    Coordinate::from(self.extent) - 1,
+                );
+                self.rain_on(p, generator);
+            }
+        }
+
+        if let Some((columns, change_all)) = self.weather.next_tile_change(self.extent.width) {
+            for x in columns {
+                for y in 0..self.extent.height {
+                    if change_all
+                        || generator.generate_integer_up_to(Self::PARTIAL_TILE_CHANGE - 1) < 4
+                    {
+                        self.apply_season_to_tile(Coordinate::new(x, y), generator);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lets a drop of rain fall on the tile at `p`, putting out any fire and leaving a little
+    /// water behind.
+    fn rain_on(&mut self, p: Coordinate, generator: &mut dyn Generator) {
+        self.extinguish_fire(p);
+        self.add_puddle(p, generator);
+    }
+
+    /// Adds a little water to the tile at `p`, unless something there blocks water.
+    fn add_puddle(&mut self, p: Coordinate, generator: &mut dyn Generator) {
+        if self.tile_map.flags(p).contains(TileFlags::BLOCKS_WATER) {
+            return;
+        }
+
+        let rained_into_water = self
+            .modify_water(p, |water| {
+                let new_depth = water.depth() + 1;
+                water.set_depth(new_depth, generator);
+                water.set_inert(false);
+            })
+            .is_some();
+        if !rained_into_water {
+            self.add_water(p, WaterNode::new(p, 1, 1, generator));
+        }
+    }
+
+    /// Changes the tile at `p` to suit the current season. In winter, snow covers grass and rock
+    /// that isn't under water or a construction, and at other times of the year it melts away.
+    fn apply_season_to_tile(&mut self, p: Coordinate, generator: &mut dyn Generator) {
+        let height = self.height_map.get_value(p.x, p.y);
+        if self.weather.season().map_or(false, Season::is_winter) {
+            let exposed = match self.tile_map.tile_type(p) {
+                TileType::Grass | TileType::Rock => {
+                    !self.tile_map.has_water(p) && self.tile_map.construction(p) < 0
+                }
+                _ => false,
+            };
+            if exposed {
+                self.tile_map.cover_with_snow(p, height, generator);
+            }
+        } else if let Some(tile_type) = self.tile_map.melt_snow(p, height, generator) {
+            if tile_type == TileType::Grass
+                && generator.generate_integer_up_to(Self::MELT_MUD_CHANCE - 1) == 0
+            {
+                self.tile_map
+                    .reset_type_and_height(p, TileType::Mud, height, generator);
+            } else if generator.generate_integer_up_to(Self::MELT_WATER_CHANCE - 1) == 0 {
+                self.add_puddle(p, generator);
+            }
+        }
+
+        self.tile_map
+            .recolor_grass(p, height, self.weather.temperature(), generator);
+        self.dirty_chunks.mark(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::map::Deposit;
+
+    #[test]
+    fn seasonal_sweep_goes_across_the_map_once() {
+        let mut weather = Weather::new();
+        weather.apply_season(Season::Spring, 1500);
+        assert_eq!(
+            weather.temperature(),
+            Weather::season_temperature(Season::Spring)
+        );
+
+        let mut columns = vec![];
+        while let Some((range, change_all)) = weather.next_tile_change(1500) {
+            assert!(change_all);
+            columns.extend(range);
+        }
+        assert_eq!(columns, (0..1500).collect::<Vec<_>>());
+        assert!(weather.next_tile_change(1500).is_none());
+    }
+
+    #[test]
+    fn snow_melts_back_into_what_it_covered() {
+        let mut generator = DefaultGenerator::default_with_seed(17);
+        let mut map = Map::new(Size::new(20, 20));
+        let grass = Coordinate::new(5, 5);
+        let rock = Coordinate::new(6, 5);
+        map.set_tile_type(rock, TileType::Rock, &mut generator);
+        let deposit = Deposit {
+            mineral: 0,
+            amount: 10,
+        };
+        *map.tile_map.deposit_mut(rock) = Some(deposit);
+
+        let mut sweep = |map: &mut Map, season| {
+            map.set_season(season);
+            for _ in 0..map.extent.width {
+                map.update_weather(&mut generator);
+            }
+        };
+
+        sweep(&mut map, Season::Winter);
+        assert_eq!(map.tile_type(grass), TileType::Snow);
+        assert_eq!(map.tile_type(rock), TileType::Snow);
+        assert_eq!(map.tile_map.deposit(rock), Some(deposit));
+
+        sweep(&mut map, Season::Spring);
+        assert!(!map.tile_map.is_snow_covered(grass));
+        assert_ne!(map.tile_type(grass), TileType::Snow);
+        assert_eq!(map.tile_type(rock), TileType::Rock);
+        assert_eq!(map.tile_map.deposit(rock), Some(deposit));
+    }
+}
@@ -0,0 +1,297 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::base::Size;
+use crate::game::game_data::map::{CacheTile, MapExtentHelper};
+use crate::util::extras::Array2DCoordinateAccessor;
+use crate::util::Array2D;
+use itertools::iproduct;
+use std::collections::{HashMap, VecDeque};
+
+/// Splits the walkable tiles of the map into zones of tiles that can all be walked between, so
+/// whether one tile can be reached from another is a matter of comparing their zones.
+///
+/// The zones are kept up to date as tiles become walkable or stop being walkable, only going over
+/// the zones the change touches.
+pub struct Zones {
+    extent: Size,
+    /// The zone of each tile, row by row, or [`Zones::NONE`] for tiles that can't be walked on.
+    labels: Vec<u32>,
+    /// How many tiles are in each zone.
+    sizes: HashMap<u32, usize>,
+    next_label: u32,
+}
+
+impl Zones {
+    const NONE: u32 = 0;
+
+    pub fn new(extent: Size) -> Self {
+        Self {
+            extent,
+            labels: vec![Self::NONE; extent.area() as usize],
+            sizes: HashMap::new(),
+            next_label: Self::NONE + 1,
+        }
+    }
+
+    fn index(&self, p: Coordinate) -> usize {
+        (p.y * self.extent.width + p.x) as usize
+    }
+
+    /// The zone of the tile at `p`, if it can be walked on.
+    pub fn zone(&self, p: Coordinate) -> Option<u32> {
+        if !self.extent.is_inside(p) {
+            return None;
+        }
+        Some(self.labels[self.index(p)]).filter(|&label| label != Self::NONE)
+    }
+
+    /// Whether `b` can be walked to from `a`.
+    pub fn reachable(&self, a: Coordinate, b: Coordinate) -> bool {
+        match (self.zone(a), self.zone(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Works out every zone from scratch.
+    pub fn rebuild(&mut self, tiles: &Array2D<CacheTile>) {
+        for label in &mut self.labels {
+            *label = Self::NONE;
+        }
+        self.sizes.clear();
+        for (y, x) in iproduct!(0..self.extent.height, 0..self.extent.width) {
+            let p = Coordinate::new(x, y);
+            if self.labels[self.index(p)] == Self::NONE && tiles.by_coordinate(p).is_walkable() {
+                let label = self.new_label();
+                self.relabel(tiles, p, Self::NONE, label);
+            }
+        }
+    }
+
+    /// Brings the zones up to date after the tiles at `changed` became walkable or stopped being
+    /// walkable. If a lot of tiles changed at once, the zones are worked out from scratch instead.
+    pub fn update(&mut self, tiles: &Array2D<CacheTile>, changed: &[Coordinate]) {
+        if changed.len() > self.labels.len() / 16 {
+            self.rebuild(tiles);
+            return;
+        }
+
+        let (opened, blocked): (Vec<_>, Vec<_>) = changed
+            .iter()
+            .partition(|&&p| tiles.by_coordinate(p).is_walkable());
+        // Every blocked tile is taken out before any zone is split, so that splitting only ever
+        // looks at tiles that are still walkable
+        for &p in &blocked {
+            let i = self.index(p);
+            let label = std::mem::replace(&mut self.labels[i], Self::NONE);
+            if let Some(size) = self.sizes.get_mut(&label) {
+                *size -= 1;
+                if *size == 0 {
+                    self.sizes.remove(&label);
+                }
+            }
+        }
+        for &p in &blocked {
+            self.split(tiles, p);
+        }
+        for &p in &opened {
+            self.join(tiles, p);
+        }
+    }
+
+    fn new_label(&mut self) -> u32 {
+        let label = self.next_label;
+        self.next_label = self.next_label.wrapping_add(1).max(Self::NONE + 1);
+        label
+    }
+
+    /// The tiles next to `p` that are in a zone, going around it clockwise from the top left.
+    fn ring(&self, tiles: &Array2D<CacheTile>, p: Coordinate) -> [Option<Coordinate>; 8] {
+        const RING: [(i32, i32); 8] = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+        ];
+        let mut ring = [None; 8];
+        for (k, &(dx, dy)) in RING.iter().enumerate() {
+            let n = p + Coordinate::new(dx, dy);
+            if self.zone(n).is_some() && tiles.by_coordinate(n).is_walkable() {
+                ring[k] = Some(n);
+            }
+        }
+
+        ring
+    }
+
+    /// Adds the newly walkable tile at `p` to the zone around it, merging the zones it connects.
+    fn join(&mut self, tiles: &Array2D<CacheTile>, p: Coordinate) {
+        let i = self.index(p);
+        if self.labels[i] != Self::NONE {
+            return;
+        }
+
+        let mut neighbors: Vec<_> = self
+            .ring(tiles, p)
+            .iter()
+            .flatten()
+            .map(|&n| (self.labels[self.index(n)], n))
+            .collect();
+        neighbors.sort_by_key(|&(label, _)| label);
+        neighbors.dedup_by_key(|&mut (label, _)| label);
+
+        // The largest zone keeps its label, and the smaller ones are relabeled to match it
+        let largest = neighbors
+            .iter()
+            .map(|&(label, _)| label)
+            .max_by_key(|label| self.sizes.get(label).copied().unwrap_or(0));
+        let label = largest.unwrap_or_else(|| self.new_label());
+        self.labels[i] = label;
+        *self.sizes.entry(label).or_insert(0) += 1;
+        for (other, n) in neighbors {
+            if other != label {
+                self.relabel(tiles, n, other, label);
+            }
+        }
+    }
+
+    /// Splits up the zone around `p`, which has just been blocked, if `p` was the only thing
+    /// connecting its parts.
+    fn split(&mut self, tiles: &Array2D<CacheTile>, p: Coordinate) {
+        // If the neighbors stay connected around `p`, the zone is still in one piece. Each run of
+        // connected neighbors starts at the one not joined to the neighbor before it, going round
+        // the ring, so a run that wraps past the top left corner is only counted once. Neighbors
+        // joined all the way round have no start at all, but are still a single run.
+        let ring = self.ring(tiles, p);
+        let mut runs = vec![];
+        for (k, n) in ring.iter().enumerate() {
+            if let Some(n) = *n {
+                let previous = ring[(k + 7) % 8];
+                // The tiles straight above, below and to either side touch diagonally
+                let joined = previous.is_some() || (k % 2 == 1 && ring[(k + 6) % 8].is_some());
+                if !joined {
+                    runs.push(n);
+                }
+            }
+        }
+        if runs.len() <= 1 {
+            return;
+        }
+
+        // Each part gets a label of its own, though parts that turn out to still be connected
+        // some other way end up sharing one
+        let mut fresh = vec![];
+        for n in runs {
+            let label = self.labels[self.index(n)];
+            if !fresh.contains(&label) {
+                let new = self.new_label();
+                self.relabel(tiles, n, label, new);
+                fresh.push(new);
+            }
+        }
+    }
+
+    /// Gives the label `new` to the tile at `from` and every walkable tile connected to it that is
+    /// labeled `old`.
+    fn relabel(&mut self, tiles: &Array2D<CacheTile>, from: Coordinate, old: u32, new: u32) {
+        let mut count = 0;
+        let mut queue = VecDeque::new();
+        let i = self.index(from);
+        self.labels[i] = new;
+        queue.push_back(from);
+        while let Some(p) = queue.pop_front() {
+            count += 1;
+            for (dy, dx) in iproduct!(-1..=1, -1..=1) {
+                let n = p + Coordinate::new(dx, dy);
+                if n == p || !self.extent.is_inside(n) {
+                    continue;
+                }
+                let j = self.index(n);
+                if self.labels[j] == old && tiles.by_coordinate(n).is_walkable() {
+                    self.labels[j] = new;
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        if old != Self::NONE {
+            self.sizes.remove(&old);
+        }
+        *self.sizes.entry(new).or_insert(0) += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_data::map::tests::grass_map;
+    use crate::game::game_data::water_node::WaterNode;
+
+    #[test]
+    fn zones_split_and_join_as_walkability_changes() {
+        let (mut map, mut generator) = grass_map(Size::new(19, 22), 24);
+        map.update_cache();
+        let (west, east) = (Coordinate::new(2, 2), Coordinate::new(17, 19));
+        assert!(map.reachable(west, east));
+
+        // A river right across the map splits it in two
+        for y in 0..22 {
+            let p = Coordinate::new(10, y);
+            map.add_water(
+                p,
+                WaterNode::new(p, WaterNode::RIVER_DEPTH, 0, &mut generator),
+            );
+        }
+        map.update_cache();
+        assert!(!map.reachable(west, east));
+        assert!(map.reachable(west, Coordinate::new(9, 21)));
+        assert!(!map.reachable(west, Coordinate::new(10, 5)));
+
+        // Drying up a single tile of it joins the two sides again
+        map.remove_water(Coordinate::new(10, 21));
+        map.update_cache();
+        assert!(map.reachable(west, east));
+    }
+
+    #[test]
+    fn blocking_a_tile_beside_a_wall_leaves_the_zone_whole() {
+        let (mut map, _) = grass_map(Size::new(7, 6), 22);
+        // A wall right next to the tile that gets blocked, so that the tiles around it form a
+        // single run that wraps past the start of the ring
+        map.tile_map.set_walkable(Coordinate::new(4, 2), false);
+        map.update_cache();
+        let blocked = Coordinate::new(3, 2);
+        let label = map.zones.zone(Coordinate::new(4, 3)).unwrap();
+
+        map.tile_map.set_walkable(blocked, false);
+        map.dirty_chunks.mark(blocked);
+        map.update_cache();
+
+        assert_eq!(map.zones.zone(Coordinate::new(4, 3)), Some(label));
+        assert_eq!(map.zones.zone(Coordinate::new(0, 0)), Some(label));
+        assert_eq!(map.zones.zone(Coordinate::new(6, 5)), Some(label));
+        assert!(map.zones.zone(blocked).is_none());
+    }
+}
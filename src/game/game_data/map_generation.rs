@@ -0,0 +1,799 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::coordinate::Coordinate;
+use crate::data::map_presets::{MapGenerationPreset, StepDefinition};
+use crate::data::minerals::Mineral;
+use crate::data::nature_objects::NatureObjectPresets;
+use crate::data::random::Generator;
+use crate::data::settings::Settings;
+use crate::data::Data;
+use crate::game::game_data::GameData;
+use snafu::Snafu;
+use std::borrow::Cow;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("There is no map generation step named '{}'", name))]
+    UnknownStep { name: String },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// One step of generating a map, such as digging the river or placing hills.
+///
+/// Mods add their own steps to every pipeline by registering them with a [`StepRegistry`].
+pub trait MapGenerationStep {
+    /// The name other steps are inserted relative to.
+    fn name(&self) -> Cow<'_, str>;
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        features: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    );
+}
+
+/// The features generated by earlier steps that later steps need to know about.
+#[derive(Debug, Default)]
+pub struct MapFeatures {
+    pub rivers: Vec<RiverCurve>,
+    pub lakes: Vec<Lake>,
+}
+
+/// The bezier curve a river was dug along.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RiverCurve {
+    pub px: [i32; 4],
+    pub py: [i32; 4],
+    /// The radius the river was dug with at its widest.
+    pub radius: f32,
+    /// The index of the river this one flows into, if it is a tributary. A tributary ends where it
+    /// meets that river.
+    pub joins: Option<usize>,
+}
+
+impl RiverCurve {
+    /// The point on the curve at `t`, which goes from 0 at the start to 1 at the end.
+    pub fn point_at(&self, t: f64) -> (f64, f64) {
+        let u = 1. - t;
+        let weights = [u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t];
+        let mut point = (0., 0.);
+        for (i, weight) in weights.iter().enumerate() {
+            point.0 += weight * f64::from(self.px[i]);
+            point.1 += weight * f64::from(self.py[i]);
+        }
+        point
+    }
+
+    /// The approximate length, in tiles, of the curve.
+    pub fn length(&self) -> f64 {
+        const SEGMENTS: i32 = 100;
+
+        let mut length = 0.;
+        let mut previous = self.point_at(0.);
+        for segment in 1..=SEGMENTS {
+            let current = self.point_at(f64::from(segment) / f64::from(SEGMENTS));
+            length += (current.0 - previous.0).hypot(current.1 - previous.1);
+            previous = current;
+        }
+
+        length
+    }
+
+    pub fn start(&self) -> Coordinate {
+        Coordinate::new(self.px[0], self.py[0])
+    }
+
+    pub fn end(&self) -> Coordinate {
+        Coordinate::new(self.px[3], self.py[3])
+    }
+}
+
+/// A closed basin that was dug out to hold a lake.
+#[derive(Debug, Copy, Clone)]
+pub struct Lake {
+    pub center: Coordinate,
+    pub radius: i32,
+}
+
+/// The ordered steps that generate a map.
+pub struct MapGenerationPipeline {
+    steps: Vec<Box<dyn MapGenerationStep>>,
+}
+
+impl MapGenerationPipeline {
+    /// Builds the pipeline described by `preset`, with the steps taking any data tables they need
+    /// from `data`. Steps registered with `data`'s [`StepRegistry`] are inserted into it as well.
+    pub fn from_preset(preset: &MapGenerationPreset, data: &Data) -> Self {
+        let mut pipeline = Self {
+            steps: preset
+                .steps
+                .iter()
+                .map(|&s| built_in_step(s, data))
+                .collect(),
+        };
+        data.map_generation_steps.insert_into(&mut pipeline);
+
+        pipeline
+    }
+
+    pub fn steps(&self) -> impl Iterator<Item = &dyn MapGenerationStep> {
+        self.steps.iter().map(Box::as_ref)
+    }
+
+    pub fn push(&mut self, step: Box<dyn MapGenerationStep>) {
+        self.steps.push(step);
+    }
+
+    pub fn insert_before(&mut self, existing: &str, step: Box<dyn MapGenerationStep>) -> Result {
+        let index = self.position(existing)?;
+        self.steps.insert(index, step);
+        Ok(())
+    }
+
+    pub fn insert_after(&mut self, existing: &str, step: Box<dyn MapGenerationStep>) -> Result {
+        let index = self.position(existing)?;
+        self.steps.insert(index + 1, step);
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.steps
+            .iter()
+            .position(|s| s.name() == name)
+            .ok_or_else(|| Error::UnknownStep {
+                name: name.to_string(),
+            })
+    }
+}
+
+/// Where a registered step goes in a pipeline.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StepPlacement {
+    /// Right before the step with the given name.
+    Before(String),
+    /// Right after the step with the given name.
+    After(String),
+    /// After every other step.
+    Last,
+}
+
+/// Creates a new step for each pipeline a registered step is inserted into.
+type StepFactory = Box<dyn Fn() -> Box<dyn MapGenerationStep>>;
+
+/// The steps mods have added to map generation, which are inserted into the pipeline of every
+/// map preset.
+///
+/// A step placed relative to a step that a preset doesn't have, such as one placed after the bogs
+/// in a preset without any, is left out of that preset's pipeline.
+#[derive(Default)]
+pub struct StepRegistry {
+    steps: Vec<(StepPlacement, StepFactory)>,
+}
+
+impl StepRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a step to map generation, placed according to `placement`. Since every pipeline gets
+    /// a step of its own, steps are registered with a function that creates one.
+    pub fn register<F>(&mut self, placement: StepPlacement, create_step: F)
+    where
+        F: Fn() -> Box<dyn MapGenerationStep> + 'static,
+    {
+        self.steps.push((placement, Box::new(create_step)));
+    }
+
+    /// Inserts the registered steps into `pipeline`, in the order they were registered.
+    pub fn insert_into(&self, pipeline: &mut MapGenerationPipeline) {
+        for (placement, create_step) in &self.steps {
+            let step = create_step();
+            // Not every preset has every step, so a step placed relative to a missing one is left
+            // out rather than failing the whole map
+            let _ = match placement {
+                StepPlacement::Before(existing) => pipeline.insert_before(existing, step),
+                StepPlacement::After(existing) => pipeline.insert_after(existing, step),
+                StepPlacement::Last => {
+                    pipeline.push(step);
+                    Ok(())
+                }
+            };
+        }
+    }
+}
+
+/// How far along a map's generation is. Each call to [`GameData::generate_map`] runs one step.
+pub struct MapGenerationState {
+    pipeline: MapGenerationPipeline,
+    next_step: usize,
+    pub features: MapFeatures,
+}
+
+impl MapGenerationState {
+    pub fn new(pipeline: MapGenerationPipeline) -> Self {
+        Self {
+            pipeline,
+            next_step: 0,
+            features: MapFeatures::default(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_step >= self.pipeline.steps.len()
+    }
+
+    /// The name of the step that will run next, if any.
+    pub fn next_step_name(&self) -> Option<Cow<'_, str>> {
+        self.pipeline.steps.get(self.next_step).map(|s| s.name())
+    }
+
+    pub(super) fn run_next_step(
+        &mut self,
+        game_data: &mut GameData,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) {
+        if let Some(step) = self.pipeline.steps.get(self.next_step) {
+            step.generate(game_data, &mut self.features, generator, settings);
+            self.next_step += 1;
+        }
+    }
+}
+
+fn built_in_step(definition: StepDefinition, data: &Data) -> Box<dyn MapGenerationStep> {
+    match definition {
+        StepDefinition::ClearHeightMap => Box::new(ClearHeightMap),
+        StepDefinition::River {
+            count,
+            width,
+            depth,
+        } => Box::new(River {
+            count,
+            width,
+            depth,
+        }),
+        StepDefinition::Tributaries {
+            count,
+            width,
+            depth,
+        } => Box::new(Tributaries {
+            count,
+            width,
+            depth,
+        }),
+        StepDefinition::Lakes { count, radius } => Box::new(Lakes { count, radius }),
+        StepDefinition::Hills { density } => Box::new(Hills { density }),
+        StepDefinition::Smoothing => Box::new(Smoothing),
+        StepDefinition::Tiles { rock_height } => Box::new(Tiles { rock_height }),
+        StepDefinition::Naturify => Box::new(Naturify {
+            nature_objects: data.nature_objects.clone(),
+        }),
+        StepDefinition::Deposits { density } => Box::new(Deposits {
+            minerals: data.minerals.all().to_vec(),
+            density,
+        }),
+        StepDefinition::Bogs { count, radius } => Box::new(Bogs { count, radius }),
+        StepDefinition::RandomizeWind => Box::new(RandomizeWind),
+        StepDefinition::CalculateFlow => Box::new(CalculateFlow),
+        StepDefinition::StartSite => Box::new(StartSite),
+        StepDefinition::UpdateCache => Box::new(UpdateCache),
+    }
+}
+
+struct ClearHeightMap;
+
+impl MapGenerationStep for ClearHeightMap {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("clear_height_map")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        _: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.map.height_map.clear();
+    }
+}
+
+struct River {
+    count: u32,
+    width: f64,
+    depth: f64,
+}
+
+impl MapGenerationStep for River {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("river")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        features: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) {
+        for _ in 0..self.count {
+            let river = game_data.generate_river(self.width, self.depth, generator, settings);
+            features.rivers.push(river);
+        }
+    }
+}
+
+struct Tributaries {
+    count: u32,
+    width: f64,
+    depth: f64,
+}
+
+impl MapGenerationStep for Tributaries {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("tributaries")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        features: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) {
+        let main_rivers: Vec<_> = (0..features.rivers.len())
+            .filter(|&i| features.rivers[i].joins.is_none())
+            .collect();
+        if main_rivers.is_empty() {
+            return;
+        }
+
+        for _ in 0..self.count {
+            let joins = generator.select(&main_rivers);
+            let tributary = game_data.generate_tributary(
+                &features.rivers[joins],
+                joins,
+                self.width,
+                self.depth,
+                generator,
+                settings,
+            );
+            if let Some(tributary) = tributary {
+                features.rivers.push(tributary);
+            }
+        }
+    }
+}
+
+struct Lakes {
+    count: f64,
+    radius: f64,
+}
+
+impl MapGenerationStep for Lakes {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("lakes")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        features: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) {
+        let lakes = game_data.generate_lakes(self.count, self.radius, generator, settings);
+        features.lakes.extend(lakes);
+    }
+}
+
+struct Hills {
+    density: f64,
+}
+
+impl MapGenerationStep for Hills {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("hills")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.generate_hills(self.density, generator);
+    }
+}
+
+struct Smoothing;
+
+impl MapGenerationStep for Smoothing {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("smoothing")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.generate_smoothing(generator);
+    }
+}
+
+struct Tiles {
+    rock_height: f32,
+}
+
+impl MapGenerationStep for Tiles {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("tiles")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        features: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.generate_tiles(self.rock_height, generator);
+        game_data.fill_lakes(&features.lakes, generator);
+    }
+}
+
+struct Naturify {
+    nature_objects: NatureObjectPresets,
+}
+
+impl MapGenerationStep for Naturify {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("naturify")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        for x in 0..game_data.map.extent.width {
+            for y in 0..game_data.map.extent.height {
+                game_data
+                    .map
+                    .naturify(Coordinate::new(x, y), &self.nature_objects, generator);
+            }
+        }
+    }
+}
+
+struct Deposits {
+    minerals: Vec<Mineral>,
+    density: f64,
+}
+
+impl MapGenerationStep for Deposits {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("deposits")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.generate_deposits(&self.minerals, self.density, generator);
+    }
+}
+
+struct Bogs {
+    count: f64,
+    radius: f64,
+}
+
+impl MapGenerationStep for Bogs {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("bogs")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.generate_bog(self.count, self.radius, generator);
+    }
+}
+
+struct RandomizeWind;
+
+impl MapGenerationStep for RandomizeWind {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("randomize_wind")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.map.randomize_wind(generator);
+    }
+}
+
+struct CalculateFlow;
+
+impl MapGenerationStep for CalculateFlow {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("calculate_flow")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        features: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.map.calculate_flow(&features.rivers, generator);
+    }
+}
+
+struct StartSite;
+
+impl MapGenerationStep for StartSite {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("start_site")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        generator: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.generate_start_site(generator);
+    }
+}
+
+struct UpdateCache;
+
+impl MapGenerationStep for UpdateCache {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("update_cache")
+    }
+
+    fn generate(
+        &self,
+        game_data: &mut GameData,
+        _: &mut MapFeatures,
+        _: &mut dyn Generator,
+        _: &Settings,
+    ) {
+        game_data.map.update_cache();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::base::Size;
+    use crate::data::random::DefaultGenerator;
+    use crate::game::game_data::water_node::WaterNode;
+    use crate::game::game_data::TileType;
+    use itertools::iproduct;
+
+    #[test]
+    fn map_summaries_count_the_features_and_tiles_of_the_map() {
+        let mut generator = DefaultGenerator::default_with_seed(11);
+        let mut game_data = GameData::new();
+        game_data.reset(Size::new(30, 12));
+        game_data.seed = 11;
+
+        let mut state = MapGenerationState::new(MapGenerationPipeline { steps: vec![] });
+        let river = RiverCurve {
+            px: [0, 5, 10, 15],
+            py: [6, 6, 6, 6],
+            radius: 1.,
+            joins: None,
+        };
+        state.features.rivers = vec![
+            river,
+            RiverCurve {
+                joins: Some(0),
+                ..river
+            },
+        ];
+        state.features.lakes.push(Lake {
+            center: Coordinate::new(25, 3),
+            radius: 2,
+        });
+        for x in 0..15 {
+            let p = Coordinate::new(x, 6);
+            game_data
+                .map
+                .add_water(p, WaterNode::new(p, 5, 0, &mut generator));
+        }
+        game_data
+            .map
+            .set_tile_type(Coordinate::new(1, 1), TileType::Bog, &mut generator);
+
+        let summary = game_data.map_summary(&state);
+
+        assert_eq!(summary.seed, 11);
+        assert_eq!((summary.width, summary.height), (30, 12));
+        assert_eq!(
+            (summary.rivers, summary.tributaries, summary.lakes),
+            (1, 1, 1)
+        );
+        assert_eq!(summary.water_tiles, 15);
+        assert_eq!(summary.bog_size, 1);
+        assert_eq!(summary.tile_counts.values().sum::<usize>(), 30 * 12);
+        assert!(summary.river_length > 25. && summary.river_length < 31.);
+    }
+
+    struct NamedStep(&'static str);
+
+    impl MapGenerationStep for NamedStep {
+        fn name(&self) -> Cow<'_, str> {
+            Cow::Borrowed(self.0)
+        }
+
+        fn generate(
+            &self,
+            _: &mut GameData,
+            _: &mut MapFeatures,
+            _: &mut dyn Generator,
+            _: &Settings,
+        ) {
+        }
+    }
+
+    fn pipeline(names: &[&'static str]) -> MapGenerationPipeline {
+        MapGenerationPipeline {
+            steps: names
+                .iter()
+                .map(|&name| Box::new(NamedStep(name)) as Box<dyn MapGenerationStep>)
+                .collect(),
+        }
+    }
+
+    fn step_names(pipeline: &MapGenerationPipeline) -> Vec<String> {
+        pipeline.steps().map(|s| s.name().into_owned()).collect()
+    }
+
+    #[test]
+    fn steps_are_inserted_next_to_the_step_they_are_placed_relative_to() {
+        let mut pipeline = pipeline(&["Hills", "River", "Bogs"]);
+
+        pipeline
+            .insert_before("River", Box::new(NamedStep("Canyon")))
+            .unwrap();
+        pipeline
+            .insert_after("Bogs", Box::new(NamedStep("Swamp gas")))
+            .unwrap();
+        assert!(pipeline
+            .insert_after("Volcano", Box::new(NamedStep("Lava")))
+            .is_err());
+
+        assert_eq!(
+            step_names(&pipeline),
+            ["Hills", "Canyon", "River", "Bogs", "Swamp gas"]
+        );
+    }
+
+    #[test]
+    fn registered_steps_go_into_every_pipeline_that_has_a_place_for_them() {
+        let mut registry = StepRegistry::new();
+        registry.register(StepPlacement::After("Hills".to_string()), || {
+            Box::new(NamedStep("Cliffs"))
+        });
+        registry.register(StepPlacement::Before("Bogs".to_string()), || {
+            Box::new(NamedStep("Reeds"))
+        });
+        registry.register(StepPlacement::Last, || Box::new(NamedStep("Ruins")));
+
+        let mut marsh = pipeline(&["Hills", "Bogs"]);
+        registry.insert_into(&mut marsh);
+        let mut arid = pipeline(&["Hills"]);
+        registry.insert_into(&mut arid);
+
+        assert_eq!(
+            step_names(&marsh),
+            ["Hills", "Cliffs", "Reeds", "Bogs", "Ruins"]
+        );
+        assert_eq!(step_names(&arid), ["Hills", "Cliffs", "Ruins"]);
+    }
+
+    #[test]
+    fn lakes_are_filled_with_water_right_up_to_their_shore() {
+        let mut generator = DefaultGenerator::default_with_seed(5);
+        let mut game_data = GameData::new();
+        game_data.reset(Size::new(40, 40));
+        let lake = Lake {
+            center: Coordinate::new(20, 20),
+            radius: 4,
+        };
+        let in_lake = |p: Coordinate| p.straight_line_distance_to(lake.center) <= 4.;
+        for (y, x) in iproduct!(0..40, 0..40) {
+            if in_lake(Coordinate::new(x, y)) {
+                game_data.map.height_map.set_value(x, y, -2.);
+            }
+        }
+
+        let mut features = MapFeatures::default();
+        features.lakes.push(lake);
+        Tiles { rock_height: 10. }.generate(
+            &mut game_data,
+            &mut features,
+            &mut generator,
+            &Settings::default(),
+        );
+
+        for (y, x) in iproduct!(0..40, 0..40) {
+            let p = Coordinate::new(x, y);
+            assert_eq!(game_data.map.water(p).is_some(), in_lake(p), "at {:?}", p);
+        }
+    }
+
+    #[test]
+    fn tributaries_run_from_the_edge_of_the_map_to_the_river_they_join() {
+        let mut generator = DefaultGenerator::default_with_seed(13);
+        let mut game_data = GameData::new();
+        game_data.reset(Size::new(300, 300));
+        let river = RiverCurve {
+            px: [0, 100, 200, 299],
+            py: [150; 4],
+            radius: 3.,
+            joins: None,
+        };
+
+        for _ in 0..10 {
+            let tributary = game_data
+                .generate_tributary(&river, 0, 0.5, 0.5, &mut generator, &Settings::default())
+                .unwrap();
+
+            assert_eq!(tributary.joins, Some(0));
+            let (start, end) = (tributary.start(), tributary.end());
+            assert!(
+                start.x == 0 || start.y == 0 || start.x == 299 || start.y == 299,
+                "starts at {:?}",
+                start
+            );
+            assert_eq!(end.y, 150);
+            assert!(end.x >= 75 && end.x <= 225, "ends at {:?}", end);
+        }
+    }
+}
@@ -18,32 +18,131 @@
     along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+mod calendar;
 mod construction;
 mod entity;
 mod filth_node;
 mod map;
+mod map_generation;
+mod save;
 mod water_node;
 
 mod camera;
 
 use crate::coordinate::{Coordinate, Direction};
+use crate::data::base::Size;
+use crate::data::minerals::Mineral;
+use crate::data::nature_objects::NatureObjectPresets;
 use crate::data::random::Generator;
 use crate::data::settings::Settings;
-use crate::game::game_data::filth_node::FilthNode;
-use crate::game::game_data::map::{Map, MapExtentHelper, TileType};
+use crate::game::game_data::map::{Map, MapExtentHelper};
 use crate::game::game_data::water_node::WaterNode;
+pub use calendar::{Calendar, CalendarEvent, Season, SeasonListener};
 pub use camera::Camera;
-pub use map::MapRenderData;
+use itertools::iproduct;
+pub use map::{Deposit, MapRenderData, MapSummary, TerritoryAction, TileType};
+pub use map_generation::{
+    Lake, MapFeatures, MapGenerationPipeline, MapGenerationState, MapGenerationStep, RiverCurve,
+    StepPlacement, StepRegistry,
+};
+pub use save::SaveState;
+use snafu::{OptionExt, Snafu};
+use std::path::Path;
 use tcod::line::Line;
 
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("'{}' is not a valid seed", value))]
+    InvalidSeed { value: String },
+    #[snafu(display(
+        "'{}' is not a valid map side; map sides must be between {} and {}",
+        value,
+        NewGameParameters::MIN_MAP_SIDE,
+        NewGameParameters::MAX_MAP_SIDE
+    ))]
+    InvalidMapSide { value: String },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// Everything the player decides on before a new map gets generated.
+#[derive(Copy, Clone, Debug)]
+pub struct NewGameParameters {
+    /// The seed to generate the map from; a new one is picked if this is `None`.
+    pub seed: Option<u64>,
+    pub map_size: Size,
+    /// Which of the map presets to generate the map with.
+    pub preset: usize,
+}
+
+impl NewGameParameters {
+    pub const MIN_MAP_SIDE: i32 = 150;
+    pub const MAX_MAP_SIDE: i32 = 2000;
+
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            seed: None,
+            map_size: Self::clamp_map_size(settings.map_size),
+            preset: 0,
+        }
+    }
+
+    /// Reads the parameters from text, as typed in by the player. An empty `seed` means a new one
+    /// gets picked.
+    pub fn parse(seed: &str, width: &str, height: &str, preset: usize) -> Result<Self> {
+        let seed = if seed.is_empty() {
+            None
+        } else {
+            Some(seed.parse().ok().context(InvalidSeed { value: seed })?)
+        };
+
+        Ok(Self {
+            seed,
+            map_size: Size::new(Self::parse_map_side(width)?, Self::parse_map_side(height)?),
+            preset,
+        })
+    }
+
+    pub fn parse_map_side(side: &str) -> Result<i32> {
+        side.parse()
+            .ok()
+            .filter(|&side| Self::is_valid_map_side(side))
+            .context(InvalidMapSide { value: side })
+    }
+
+    pub fn is_valid_map_side(side: i32) -> bool {
+        side >= Self::MIN_MAP_SIDE && side <= Self::MAX_MAP_SIDE
+    }
+
+    /// Brings each side of `map_size` within the sizes a map can have, such as for a map size read
+    /// from a settings file that has been edited by hand.
+    pub fn clamp_map_size(map_size: Size) -> Size {
+        Size::new(
+            map_size
+                .width
+                .max(Self::MIN_MAP_SIDE)
+                .min(Self::MAX_MAP_SIDE),
+            map_size
+                .height
+                .max(Self::MIN_MAP_SIDE)
+                .min(Self::MAX_MAP_SIDE),
+        )
+    }
+}
+
 pub struct GameData {
     pub running: bool,
     pub map: Map,
+    /// The seed the current map was generated from.
+    pub seed: u64,
+    /// Which of the map presets the current map was generated with.
+    pub preset: usize,
+    /// Where the camp was started, which the camera is centered on when the game begins.
+    pub camp_center: Coordinate,
+    pub calendar: Calendar,
+    season_listeners: Vec<Box<dyn SeasonListener>>,
     /*
     int screenWidth, screenHeight;
-    Season season;
-    int time;
-    int age;
     int orcCount, goblinCount;
     unsigned int peacefulFaunaCount;
     bool paused;
@@ -84,15 +183,44 @@ impl GameData {
         Direction::South,
     ];
 
+    /// The side length of the square map the original game's generation constants were tuned for.
+    const REFERENCE_MAP_SIDE: i32 = 500;
+
+    /// How many times per second the game world is updated.
+    pub const UPDATES_PER_SECOND: i32 = 25;
+
     pub fn new() -> Self {
         Self {
             running: false,
-            map: Map::new(),
+            map: Map::new(Settings::DEFAULT_MAP_SIZE),
+            seed: 0,
+            preset: 0,
+            camp_center: Coordinate::from(Settings::DEFAULT_MAP_SIZE) / 2,
+            calendar: Calendar::new(),
+            season_listeners: vec![],
+        }
+    }
+
+    /// Everything about the current game that goes into a save file.
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            version: SaveState::VERSION,
+            seed: self.seed,
+            map_size: self.map.extent,
+            preset: self.preset,
+            calendar: self.calendar,
         }
     }
 
-    pub fn reset(&mut self) {
-        self.map = Map::new();
+    /// Writes the current game to a save file at `save_file_path`.
+    pub fn save<P: AsRef<Path>>(&self, save_file_path: P) -> save::Result {
+        self.save_state().save(save_file_path)
+    }
+
+    pub fn reset(&mut self, map_size: Size) {
+        self.map = Map::new(map_size);
+        self.camp_center = Coordinate::from(map_size) / 2;
+        self.calendar = Calendar::new();
         // TODO: Finish!
         /*
         instance->npcList.clear();
@@ -132,60 +260,107 @@ impl GameData {
         //unimplemented!()
     }
 
+    /// Runs the next step of generating the map.
     pub fn generate_map(
         &mut self,
         generator: &mut dyn Generator,
         settings: &Settings,
-        state: Option<&mut MapGenerationState>,
-    ) -> Option<MapGenerationState> {
-        use MapGenerationStage::*;
+        state: &mut MapGenerationState,
+    ) {
+        state.run_next_step(self, generator, settings);
+    }
 
-        if state.is_none() {
-            return Some(MapGenerationState::new());
+    /// Runs every map generation step in one go, for when there is no loading screen to update.
+    pub fn generate_complete_map(
+        &mut self,
+        pipeline: MapGenerationPipeline,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) -> MapGenerationState {
+        let mut state = MapGenerationState::new(pipeline);
+        while !state.is_done() {
+            self.generate_map(generator, settings, &mut state);
         }
-        let state = state.unwrap();
 
-        match state.stage {
-            HeightMapClear => {
-                self.map.height_map.clear();
-            }
-            GenerateRiver => {
-                self.generate_river(&mut state.px, &mut state.py, generator, settings);
-            }
-            GenerateHills => {
-                self.generate_hills(generator);
-            }
-            GenerateSmoothing => {
-                self.generate_smoothing(generator);
-            }
-            GenerateTiles => {
-                self.generate_tiles(generator);
-            }
-            Naturify => {
-                for x in 0..self.map.extent.width {
-                    for y in 0..self.map.extent.height {
-                        self.map.naturify(Coordinate::new(x, y));
-                    }
-                }
-            }
-            GenerateBog => {
-                self.generate_bog(generator);
-            }
-            RandomizeWind => {
-                self.map.randomize_wind(generator);
-            }
-            CalculateFlow => {
-                self.map.calculate_flow(state.px, state.py, generator);
-            }
-            UpdateCache => {
-                self.map.update_cache();
-            }
+        state
+    }
+
+    /// Starts the first season of a new game.
+    pub fn begin(&mut self) {
+        self.season_changed(self.calendar.season());
+    }
+
+    /// Advances the game world by one tick.
+    pub fn update(&mut self, nature_objects: &NatureObjectPresets, generator: &mut dyn Generator) {
+        if let Some(CalendarEvent::NewSeason(season)) = self.calendar.advance() {
+            self.season_changed(season);
+        }
+        self.map.update_weather(generator);
+        self.map.update_water(generator);
+        self.map.update_ice(nature_objects, generator);
+        self.map.update_filth(generator);
+        self.map.update_fire(generator);
+        self.map.update_corruption(generator);
+        self.map.update_regrowth(nature_objects, generator);
+        self.map.update_markers();
+        self.map.update_cache();
+        self.map.update_paths();
+    }
 
-            Done => (),
+    pub fn add_season_listener(&mut self, listener: Box<dyn SeasonListener>) {
+        self.season_listeners.push(listener);
+    }
+
+    pub fn remove_season_listener(&mut self, name: &str) -> Option<Box<dyn SeasonListener>> {
+        let index = self
+            .season_listeners
+            .iter()
+            .position(|l| l.name() == name)?;
+        Some(self.season_listeners.remove(index))
+    }
+
+    /// Everything that happens when a new season begins.
+    fn season_changed(&mut self, season: Season) {
+        self.map.set_season(season);
+        // TODO: Spawn migrants and raids, and count down the safe months
+
+        // The listeners get the whole game to work with, so they are set aside while they run
+        let listeners = std::mem::take(&mut self.season_listeners);
+        for listener in &listeners {
+            listener.season_changed(self, season);
         }
+        self.season_listeners.extend(listeners);
+    }
 
-        state.stage.next();
-        None
+    pub fn map_summary(&self, state: &MapGenerationState) -> MapSummary {
+        MapSummary {
+            seed: self.seed,
+            width: self.map.extent.width,
+            height: self.map.extent.height,
+            tile_counts: self.map.tile_type_counts(),
+            water_tiles: self.map.count_water_tiles(),
+            rivers: state
+                .features
+                .rivers
+                .iter()
+                .filter(|r| r.joins.is_none())
+                .count(),
+            tributaries: state
+                .features
+                .rivers
+                .iter()
+                .filter(|r| r.joins.is_some())
+                .count(),
+            lakes: state.features.lakes.len(),
+            deposit_tiles: self.map.count_deposit_tiles(),
+            river_length: state
+                .features
+                .rivers
+                .iter()
+                .map(|r| self.map.river_length(r))
+                .sum(),
+            bog_size: self.map.count_tiles_of_type(TileType::Bog),
+        }
     }
 
     pub fn render_map(&mut self, render_data: MapRenderData) {
@@ -194,11 +369,13 @@ impl GameData {
 
     fn generate_river(
         &mut self,
-        px: &mut [i32; 4],
-        py: &mut [i32; 4],
+        width_multiplier: f64,
+        depth_multiplier: f64,
         generator: &mut dyn Generator,
         settings: &Settings,
-    ) {
+    ) -> RiverCurve {
+        let mut river = RiverCurve::default();
+        let RiverCurve { px, py, .. } = &mut river;
         let river_start_left = generator.generate_bool();
         let river_end_right = generator.generate_bool();
 
@@ -223,12 +400,14 @@ impl GameData {
                 py[3] = self.map.extent.height - 1;
             }
 
-            // This conditional ensures that the river's beginning and end are at least 100 units apart
-            f64::from((px[0] - px[3]).pow(2) + (py[0] - py[3]).pow(2)).sqrt() < 100.
+            // This conditional ensures that the river's beginning and end are at least 100 units
+            // apart (on a map of the reference size)
+            f64::from((px[0] - px[3]).pow(2) + (py[0] - py[3]).pow(2)).sqrt()
+                < 100. * self.map_scale()
         } {}
 
-        let depth = settings.river_depth as i32;
-        let width = settings.river_width;
+        let depth = f64::from(settings.river_depth) * depth_multiplier;
+        let width = (f64::from(settings.river_width) * width_multiplier * self.map_scale()).max(3.);
         self.map.height_map.dig_bezier(
             *px,
             *py,
@@ -237,15 +416,148 @@ impl GameData {
             width as f32,
             -depth as f32,
         );
+        river.radius = width as f32;
+
+        river
+    }
+
+    /// Digs a river from a random edge of the map to a point along `joins`, narrowing towards its
+    /// source. Returns `None` if no source far enough away from the junction could be found.
+    fn generate_tributary(
+        &mut self,
+        joins: &RiverCurve,
+        joins_index: usize,
+        width_multiplier: f64,
+        depth_multiplier: f64,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) -> Option<RiverCurve> {
+        let extent = Coordinate::from(self.map.extent);
+        let junction = joins.point_at(f64::from(generator.generate_integer(25, 75)) / 100.);
+        let junction = Coordinate::new(junction.0 as i32, junction.1 as i32);
+
+        let mut source = None;
+        for _ in 0..100 {
+            let candidate = match generator.select(&Self::RIVER_DIRECTIONS) {
+                Direction::West => {
+                    Coordinate::new(0, generator.generate_integer_up_to(extent.y - 1))
+                }
+                Direction::East => {
+                    Coordinate::new(extent.x - 1, generator.generate_integer_up_to(extent.y - 1))
+                }
+                Direction::North => {
+                    Coordinate::new(generator.generate_integer_up_to(extent.x - 1), 0)
+                }
+                _ => Coordinate::new(generator.generate_integer_up_to(extent.x - 1), extent.y - 1),
+            };
+            if f64::from(candidate.straight_line_distance_to(junction)) >= 75. * self.map_scale() {
+                source = Some(candidate);
+                break;
+            }
+        }
+        let source = source?;
+
+        // Bend the tributary a little by nudging the two middle control points off the straight
+        // line between its source and the junction
+        let wander = (20. * self.map_scale()) as i32;
+        let mut control_point = |thirds: i32| {
+            let on_line = source + (junction - source) * thirds / 3;
+            generator
+                .generate_coordinate_within_distance(on_line, wander)
+                .clamp_to_rectangle(Coordinate::ORIGIN + 10, extent - 11)
+        };
+        let (first, second) = (control_point(1), control_point(2));
+
+        let depth = f64::from(settings.river_depth) * depth_multiplier;
+        let width = (f64::from(settings.river_width) * width_multiplier * self.map_scale()).max(3.);
+        let tributary = RiverCurve {
+            px: [source.x, first.x, second.x, junction.x],
+            py: [source.y, first.y, second.y, junction.y],
+            radius: width as f32,
+            joins: Some(joins_index),
+        };
+        self.map.height_map.dig_bezier(
+            tributary.px,
+            tributary.py,
+            (width / 2.).max(2.) as f32,
+            -depth as f32,
+            width as f32,
+            -depth as f32,
+        );
+
+        Some(tributary)
+    }
+
+    /// Digs out closed basins away from the rivers, which fill with water once the tiles are
+    /// generated.
+    fn generate_lakes(
+        &mut self,
+        count_multiplier: f64,
+        radius_multiplier: f64,
+        generator: &mut dyn Generator,
+        settings: &Settings,
+    ) -> Vec<Lake> {
+        // Two lakes of radius 12 per map of the reference size
+        let radius = ((12. * radius_multiplier * self.map_scale()) as i32).max(4);
+        let lake_count = Self::scaled_count(
+            2 * self.map.extent.area() / Self::REFERENCE_MAP_SIDE.pow(2),
+            count_multiplier,
+        );
+        let depth = settings.river_depth as f32;
+
+        let mut lakes = vec![];
+        for _ in 0..lake_count {
+            let mut infinity_check = 0;
+            while infinity_check < 1000 {
+                let candidate = generator.generate_coordinate_within_rectangle(
+                    Coordinate::ORIGIN + (radius + 5),
+                    Coordinate::from(self.map.extent) - (radius + 5),
+                );
+                if self.find_river_distance(candidate) > radius + 15 {
+                    // A few overlapping basins make for a less perfectly round shore
+                    self.map.height_map.add_hill(
+                        candidate.x as f32,
+                        candidate.y as f32,
+                        radius as f32,
+                        -depth,
+                    );
+                    for _ in 0..3 {
+                        let basin =
+                            generator.generate_coordinate_within_distance(candidate, radius / 2);
+                        self.map.height_map.add_hill(
+                            basin.x as f32,
+                            basin.y as f32,
+                            (radius * 2 / 3) as f32,
+                            -depth / 2.,
+                        );
+                    }
+
+                    lakes.push(Lake {
+                        center: candidate,
+                        radius,
+                    });
+                    break;
+                }
+                infinity_check += 1;
+            }
+        }
+
+        lakes
     }
 
-    fn generate_hills(&mut self, generator: &mut dyn Generator) {
+    fn generate_hills(&mut self, density: f64, generator: &mut dyn Generator) {
         let mut hills = 0;
         let mut infinity_check = 0;
 
+        // The original placed width / 66 hills on its 500x500 map; keep the same density by area
+        let hill_count = Self::scaled_count(
+            self.map.extent.area() / (Self::REFERENCE_MAP_SIDE * 66),
+            density,
+        );
+
         // infinity_check is just there to make sure our while loop doesn't become an infinite one
         // in case no suitable hill sites are found
-        while hills < self.map.extent.width / 66 && infinity_check < 1000 {
+        while hills < hill_count && infinity_check < 1000 {
             let candidate =
                 generator.generate_coordinate_within_origin_extent(self.map.extent.into());
             if self.find_river_distance(candidate) > 35 {
@@ -292,7 +604,7 @@ impl GameData {
             .kernel_transform(&DX, &DY, &WEIGHT, 0., 1.);
     }
 
-    fn generate_tiles(&mut self, generator: &mut dyn Generator) {
+    fn generate_tiles(&mut self, rock_height: f32, generator: &mut dyn Generator) {
         // Translate heightmap values into tiles
         for x in 0..self.map.extent.width {
             for y in 0..self.map.extent.height {
@@ -326,7 +638,7 @@ impl GameData {
                         self.map.set_tile_type(p, TileType::Riverbed, generator);
                         self.create_water(p, WaterNode::RIVER_DEPTH, 0, generator);
                     }
-                } else if height < 4.5 {
+                } else if height < rock_height {
                     self.map.set_tile_type(p, TileType::Grass, generator);
                 } else {
                     self.map.set_tile_type(p, TileType::Rock, generator);
@@ -335,19 +647,57 @@ impl GameData {
         }
     }
 
-    fn generate_bog(&mut self, generator: &mut dyn Generator) {
+    /// Fills the basins dug out for `lakes` with water. Unlike along a river, the shallow edge of a
+    /// lake isn't left as a dry ditch, so even a small lake ends up holding water.
+    fn fill_lakes(&mut self, lakes: &[Lake], generator: &mut dyn Generator) {
+        for lake in lakes {
+            // The smaller basins around the center reach up to 7/6 of the radius out
+            let reach = lake.radius * 7 / 6;
+            let low = self.map.extent.shrink(lake.center - reach);
+            let high = self.map.extent.shrink(lake.center + reach);
+            for (y, x) in iproduct!(low.y..=high.y, low.x..=high.x) {
+                let p = Coordinate::new(x, y);
+                if p.straight_line_distance_to(lake.center) <= reach as f32
+                    && self.map.height_map.get_value(x, y) < self.map.water_level
+                    && self.map.water(p).is_none()
+                {
+                    self.map.set_tile_type(p, TileType::Riverbed, generator);
+                    self.create_water(p, WaterNode::RIVER_DEPTH, 0, generator);
+                }
+            }
+        }
+    }
+
+    fn generate_bog(
+        &mut self,
+        count_multiplier: f64,
+        radius_multiplier: f64,
+        generator: &mut dyn Generator,
+    ) {
+        // One bog of radius 25 per map of the reference size
+        let radius = ((25. * radius_multiplier * self.map_scale()) as i32).max(10);
+        let bog_count = Self::scaled_count(
+            self.map.extent.area() / Self::REFERENCE_MAP_SIDE.pow(2),
+            count_multiplier,
+        );
+        for _ in 0..bog_count {
+            self.generate_single_bog(radius, generator);
+        }
+    }
+
+    fn generate_single_bog(&mut self, radius: i32, generator: &mut dyn Generator) {
         // Create a bog
         let mut infinity_check = 0;
         while infinity_check < 1000 {
             let candidate = generator.generate_coordinate_within_rectangle(
-                Coordinate::ORIGIN + 30,
-                Coordinate::from(self.map.extent) - 30,
+                Coordinate::ORIGIN + (radius + 5),
+                Coordinate::from(self.map.extent) - (radius + 5),
             );
             if self.find_river_distance(candidate) > 30 {
                 let mut low_offset = generator.generate_integer(-5, 5);
                 let mut high_offset = generator.generate_integer(-5, 5);
-                for x_offset in -25..25 {
-                    let range = f64::from(25 * 25 - x_offset * x_offset).sqrt() as i32;
+                for x_offset in -radius..radius {
+                    let range = f64::from(radius * radius - x_offset * x_offset).sqrt() as i32;
                     low_offset = (generator.generate_integer(-1, 1) + low_offset)
                         .min(-5)
                         .max(5);
@@ -368,6 +718,77 @@ impl GameData {
         }
     }
 
+    /// How large the map is compared to the map size the generator was originally tuned for.
+    fn map_scale(&self) -> f64 {
+        f64::from(self.map.extent.width.min(self.map.extent.height))
+            / f64::from(Self::REFERENCE_MAP_SIDE)
+    }
+
+    fn generate_deposits(
+        &mut self,
+        minerals: &[Mineral],
+        density: f64,
+        generator: &mut dyn Generator,
+    ) {
+        let reference_maps =
+            f64::from(self.map.extent.area()) / f64::from(Self::REFERENCE_MAP_SIDE.pow(2));
+        for (mineral_index, mineral) in minerals.iter().enumerate() {
+            // Rare minerals may have less than one cluster per map, so the fraction is the chance
+            // of getting one more
+            let expected = mineral.rarity * density * reference_maps;
+            let mut clusters = expected.floor() as i32;
+            if generator.generate_floating() < expected.fract() {
+                clusters += 1;
+            }
+
+            for _ in 0..clusters {
+                let mut infinity_check = 0;
+                while infinity_check < 100 {
+                    let candidate =
+                        generator.generate_coordinate_within_origin_extent(self.map.extent.into());
+                    if self.map.tile_type(candidate) == TileType::Rock
+                        && self.map.deposit(candidate).is_none()
+                    {
+                        self.map.place_deposit_cluster(
+                            candidate,
+                            mineral_index,
+                            mineral,
+                            generator,
+                        );
+                        break;
+                    }
+                    infinity_check += 1;
+                }
+            }
+        }
+    }
+
+    /// Picks where the camp starts, clears some room for it and makes the area around it the
+    /// player's territory.
+    fn generate_start_site(&mut self, generator: &mut dyn Generator) {
+        const TRIES: u32 = 20;
+        const CAMP_RADIUS: i32 = 20;
+
+        let center = self.map.find_start_site(TRIES, generator);
+        let low = center - CAMP_RADIUS;
+        let high = center + CAMP_RADIUS;
+
+        self.map.clear_start_area(low, high, generator);
+        self.map.populate_start_area(low, high, generator);
+        self.map.set_territory_rectangle(low, high, true);
+        self.camp_center = center;
+    }
+
+    /// Scales how many of something to place by a preset's multiplier, placing at least one unless
+    /// the multiplier turns it off entirely.
+    fn scaled_count(count: i32, multiplier: f64) -> i32 {
+        if multiplier > 0. {
+            ((f64::from(count) * multiplier).round() as i32).max(1)
+        } else {
+            0
+        }
+    }
+
     fn find_river_distance(&self, candidate: Coordinate) -> i32 {
         let mut river_distance = 70;
 
@@ -398,103 +819,96 @@ impl GameData {
         time: i32,
         generator: &mut dyn Generator,
     ) {
-        //If there is filth here mix it with the water
-        let filth_depth = self.map.filth(pos).map(FilthNode::depth);
-
-        if self.map.water_mut(pos).is_none() {
-            let mut new_water = WaterNode::new(pos, amount, time, generator);
-            if let Some(filth_depth) = filth_depth {
-                new_water.set_filth(filth_depth);
-            }
-            self.map.add_water(pos, new_water);
-        } else {
-            let coordinate = {
-                let mut water = self.map.water_mut(pos).unwrap();
-                if let Some(filth_depth) = filth_depth {
-                    water.set_filth(filth_depth);
-                }
-                let new_depth = water.depth() + amount;
-                water.set_depth(new_depth, generator)
-            };
-            if let Some(pos) = coordinate {
-                self.map.add_to_cache(pos);
+        // Any filth here is mixed into the water by the map
+        let updated = self.map.modify_water(pos, |water| {
+            let new_depth = water.depth() + amount;
+            water.set_depth(new_depth, generator)
+        });
+        match updated {
+            Some(Some(pos)) => self.map.add_to_cache(pos),
+            Some(None) => {}
+            None => {
+                self.map
+                    .add_water(pos, WaterNode::new(pos, amount, time, generator));
             }
         }
+    }
+}
 
-        if filth_depth.is_some() {
-            self.map.remove_filth(pos);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn new_game_parameters_are_only_parsed_from_valid_text() {
+        let parameters = NewGameParameters::parse("", "300", "2000", 1).unwrap();
+        assert_eq!(parameters.seed, None);
+        assert_eq!(parameters.map_size, Size::new(300, 2000));
+        assert_eq!(parameters.preset, 1);
+        assert_eq!(
+            NewGameParameters::parse("42", "150", "150", 0)
+                .unwrap()
+                .seed,
+            Some(42)
+        );
+
+        assert!(NewGameParameters::parse("-1", "300", "300", 0).is_err());
+        assert!(NewGameParameters::parse("", "149", "300", 0).is_err());
+        assert!(NewGameParameters::parse("", "300", "2001", 0).is_err());
+        assert!(NewGameParameters::parse("", "", "300", 0).is_err());
     }
 
-    fn remove_filth(&mut self, filth: &FilthNode) {
-        /*
-        boost::shared_ptr<FilthNode> filth = Map::Inst()->GetFilth(pos).lock();
-        if (filth) {
-            for (std::list<boost::weak_ptr<FilthNode> >::iterator filthi = filthList.begin(); filthi != filthList.end(); ++filthi) {
-                if (filthi->lock() == filth) {
-                    filthList.erase(filthi);
-                    break;
-                }
-            }
-            Map::Inst()->SetFilth(pos, boost::shared_ptr<FilthNode>());
-        }
-        */
-        unimplemented!()
+    #[test]
+    fn map_sizes_from_the_settings_are_kept_within_bounds() {
+        let mut settings = Settings::default();
+        settings.map_size = Size::new(-5, 100_000);
+
+        assert_eq!(
+            NewGameParameters::new(&settings).map_size,
+            Size::new(
+                NewGameParameters::MIN_MAP_SIDE,
+                NewGameParameters::MAX_MAP_SIDE
+            )
+        );
     }
-}
 
-enum MapGenerationStage {
-    HeightMapClear,
-    GenerateRiver,
-    GenerateHills,
-    GenerateSmoothing,
-    GenerateTiles,
-    Naturify,
-    GenerateBog,
-    RandomizeWind,
-    CalculateFlow,
-    UpdateCache,
-
-    Done,
-}
+    struct SeasonLog(Rc<RefCell<Vec<Season>>>);
 
-impl MapGenerationStage {
-    fn next(&mut self) {
-        use MapGenerationStage::*;
-        *self = match self {
-            HeightMapClear => GenerateRiver,
-            GenerateRiver => GenerateHills,
-            GenerateHills => GenerateSmoothing,
-            GenerateSmoothing => GenerateTiles,
-            GenerateTiles => Naturify,
-            Naturify => GenerateBog,
-            GenerateBog => RandomizeWind,
-            RandomizeWind => CalculateFlow,
-            CalculateFlow => UpdateCache,
-            UpdateCache | Done => Done,
+    impl SeasonListener for SeasonLog {
+        fn name(&self) -> Cow<'_, str> {
+            Cow::Borrowed("season log")
         }
-    }
-}
-
-pub struct MapGenerationState {
-    stage: MapGenerationStage,
-    px: [i32; 4],
-    py: [i32; 4],
-}
 
-impl MapGenerationState {
-    fn new() -> Self {
-        Self {
-            stage: MapGenerationStage::HeightMapClear,
-            px: [0; 4],
-            py: [0; 4],
+        fn season_changed(&self, game_data: &mut GameData, season: Season) {
+            assert_eq!(game_data.calendar.season(), season);
+            self.0.borrow_mut().push(season);
         }
     }
 
-    pub fn is_done(&self) -> bool {
-        match self.stage {
-            MapGenerationStage::Done => true,
-            _ => false,
+    #[test]
+    fn season_listeners_hear_about_every_new_season_until_removed() {
+        let mut game_data = GameData::new();
+        game_data.reset(Size::new(12, 12));
+        let log = Rc::new(RefCell::new(vec![]));
+        game_data.add_season_listener(Box::new(SeasonLog(Rc::clone(&log))));
+
+        game_data.begin();
+        let ticks_per_month = Calendar::TICKS_PER_DAY * Calendar::DAYS_PER_MONTH;
+        for _ in 0..ticks_per_month * 2 {
+            if let Some(CalendarEvent::NewSeason(season)) = game_data.calendar.advance() {
+                game_data.season_changed(season);
+            }
         }
+        assert_eq!(
+            *log.borrow(),
+            [Season::EarlySpring, Season::Spring, Season::LateSpring]
+        );
+
+        assert!(game_data.remove_season_listener("season log").is_some());
+        game_data.season_changed(Season::EarlySummer);
+        assert_eq!(log.borrow().len(), 3);
     }
 }
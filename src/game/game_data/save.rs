@@ -0,0 +1,132 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::base::Size;
+use crate::game::game_data::Calendar;
+use serde_derive::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt, Snafu};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Cannot save game to {:?} because: {}", path, source))]
+    GameSave {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[snafu(display("Cannot load game from {:?} because: {}", path, source))]
+    GameLoad {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[snafu(display(
+        "Save file is version {}, but only version {} can be loaded",
+        found,
+        expected
+    ))]
+    UnsupportedVersion { found: u32, expected: u32 },
+    #[snafu(display("Cannot read save file: {}", source))]
+    JsonDeserialization { source: serde_json::Error },
+    #[snafu(display("Cannot write save file: {}", source))]
+    JsonSerialization { source: serde_json::Error },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// Everything about a game that gets written into a save file.
+///
+/// The map itself isn't saved yet, but since it is generated from the seed, the seed is enough to
+/// get the same map back.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SaveState {
+    pub version: u32,
+    /// The seed the map was generated from.
+    pub seed: u64,
+    pub map_size: Size,
+    /// Which of the map presets the map was generated with.
+    pub preset: usize,
+    /// How much time has passed in the game.
+    #[serde(default)]
+    pub calendar: Calendar,
+}
+
+impl SaveState {
+    /// The version of the save format written by this build.
+    pub const VERSION: u32 = 1;
+
+    pub fn load<P: AsRef<Path>>(save_file_path: P) -> Result<Self> {
+        let save_file_path = save_file_path.as_ref();
+        let save_string = fs::read_to_string(save_file_path).with_context(|| GameLoad {
+            path: save_file_path.to_path_buf(),
+        })?;
+
+        Self::from_save_string(&save_string)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, save_file_path: P) -> Result {
+        let save_file_path = save_file_path.as_ref();
+        fs::write(save_file_path, self.to_save_string()?).with_context(|| GameSave {
+            path: save_file_path.to_path_buf(),
+        })?;
+
+        Ok(())
+    }
+
+    fn from_save_string(save_string: &str) -> Result<Self> {
+        let save_state: Self = serde_json::from_str(save_string).context(JsonDeserialization)?;
+        ensure!(
+            save_state.version == Self::VERSION,
+            UnsupportedVersion {
+                found: save_state.version,
+                expected: Self::VERSION,
+            }
+        );
+
+        Ok(save_state)
+    }
+
+    fn to_save_string(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context(JsonSerialization)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_seed_and_date_survive_a_round_trip_through_a_save_file() {
+        let mut calendar = Calendar::new();
+        for _ in 0..Calendar::TICKS_PER_DAY * 45 + 7 {
+            calendar.advance();
+        }
+        let save_state = SaveState {
+            version: SaveState::VERSION,
+            seed: u64::max_value(),
+            map_size: Size::new(300, 200),
+            preset: 2,
+            calendar,
+        };
+
+        let loaded = SaveState::from_save_string(&save_state.to_save_string().unwrap()).unwrap();
+
+        assert_eq!(loaded, save_state);
+    }
+}
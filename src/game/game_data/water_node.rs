@@ -24,7 +24,7 @@ use crate::game::game_data::map::MapGraphicDrawable;
 use crate::util::tcod::Chars;
 use tcod::Color;
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct WaterNode {
     pub position: Coordinate,
     depth: i32,
@@ -39,6 +39,8 @@ pub struct WaterNode {
 
 impl WaterNode {
     pub const RIVER_DEPTH: i32 = 5000;
+    /// Water deeper than this can't be walked through.
+    pub const MAX_WALKABLE_DEPTH: i32 = 20;
 
     pub fn new(
         position: Coordinate,
@@ -62,11 +64,58 @@ impl WaterNode {
         water
     }
 
-    // AddFilth in original
+    /// How many tiles the water has spread across since leaving its river or lake. Water in rivers
+    /// and lakes is 0, and never runs dry.
+    pub fn time_from_river_bed(&self) -> i32 {
+        self.time_from_river_bed
+    }
+
+    /// Whether the water borders a tile it could spread onto but hasn't.
+    pub fn is_coastal(&self) -> bool {
+        self.coastal
+    }
+
+    pub fn set_coastal(&mut self, coastal: bool) {
+        self.coastal = coastal;
+    }
+
+    pub fn is_inert(&self) -> bool {
+        self.inert
+    }
+
+    /// Inert water had nowhere to go the last time it was updated, and rests for a while before
+    /// it tries again.
+    pub fn set_inert(&mut self, inert: bool) {
+        self.inert = inert;
+        self.inert_counter = 0;
+    }
+
+    /// Lets inert water rest for another tick, waking it up once it has rested for `rest_ticks`.
+    /// Returns whether the water should be updated this tick.
+    pub fn rest(&mut self, rest_ticks: i32) -> bool {
+        if self.inert {
+            self.inert_counter += 1;
+            if self.inert_counter > rest_ticks {
+                self.set_inert(false);
+            }
+        }
+
+        !self.inert
+    }
+
+    pub fn filth(&self) -> i32 {
+        self.filth
+    }
+
     pub fn set_filth(&mut self, filth_depth: i32) {
         self.filth = filth_depth;
     }
 
+    // AddFilth in original
+    pub fn add_filth(&mut self, filth_depth: i32) {
+        self.filth += filth_depth;
+    }
+
     pub fn depth(&self) -> i32 {
         self.depth
     }
@@ -77,7 +126,10 @@ impl WaterNode {
         new_depth: i32,
         generator: &mut dyn Generator,
     ) -> Option<Coordinate> {
-        let result = if self.depth <= 20 && new_depth <= 20 && self.depth != new_depth {
+        let result = if self.depth <= Self::MAX_WALKABLE_DEPTH
+            && new_depth <= Self::MAX_WALKABLE_DEPTH
+            && self.depth != new_depth
+        {
             Some(self.position)
         } else {
             None
@@ -19,10 +19,14 @@
 */
 
 pub mod loading_dialog;
+pub mod new_game_dialog;
 
 use crate::data::base::{Position, Size};
-use crate::game::game_data::{Camera, MapGenerationState, MapRenderData};
+use crate::game::game_data::{
+    Camera, GameData, MapGenerationPipeline, MapGenerationState, MapRenderData, NewGameParameters,
+};
 use crate::game::game_state::game::loading_dialog::LoadingDialog;
+use crate::game::game_state::game::new_game_dialog::NewGameDialog;
 use crate::game::game_state::{
     GameState, GameStateBackgroundUpdateResult, GameStateChange, GameStateError, GameStateResult,
     GameStateUpdateResult,
@@ -31,7 +35,8 @@ use crate::game::GameRef;
 use crate::ui::MessageBox;
 use slog::{info, o};
 use std::borrow::Cow;
-use tcod::{BackgroundFlag, Console};
+use std::time::{Duration, Instant};
+use tcod::{colors, BackgroundFlag, Console, TextAlignment};
 
 pub struct ConfirmNewGame;
 
@@ -55,10 +60,10 @@ impl GameState for ConfirmNewGame {
                 "Yes",
                 Box::new(|| GameStateChange::EndGame),
                 Some("No"),
-                Some(Box::new(|| Game::game_state_change(logger))),
+                Some(Box::new(|| NewGameDialog::game_state_change(logger))),
             )))
         } else {
-            Ok(Game::game_state_change(logger))
+            Ok(NewGameDialog::game_state_change(logger))
         }
     }
 
@@ -72,17 +77,50 @@ pub struct Game {
     first_run: bool,
     map_generation_state: Option<MapGenerationState>,
     camera: Camera,
+    parameters: NewGameParameters,
+    /// When the game world is next due to be updated.
+    next_tick: Option<Instant>,
 }
 
 impl Game {
     #[allow(clippy::needless_pass_by_value)]
-    pub fn game_state_change(parent_logger: slog::Logger) -> GameStateChange {
-        GameStateChange::Push(Box::new(Self {
+    pub fn game_state(
+        parent_logger: slog::Logger,
+        parameters: NewGameParameters,
+    ) -> Box<dyn GameState> {
+        Box::new(Self {
             logger: parent_logger.new(o!("GameState" => "Game")),
             first_run: true,
             map_generation_state: None,
-            camera: Camera::new(),
-        }))
+            camera: Camera::new(parameters.map_size),
+            parameters,
+            next_tick: None,
+        })
+    }
+
+    /// If the game falls further behind than this, it gives up on catching up.
+    const MAX_TICKS_PER_FRAME: u32 = 5;
+
+    /// Updates the game world as many times as it is due to be updated since it was last updated.
+    fn tick(&mut self, game_ref: &mut GameRef) {
+        let tick_length = Duration::from_millis(1000 / GameData::UPDATES_PER_SECOND as u64);
+        let now = Instant::now();
+        let mut next_tick = self.next_tick.unwrap_or(now);
+
+        let mut ticks = 0;
+        while next_tick <= now {
+            if ticks == Self::MAX_TICKS_PER_FRAME {
+                next_tick = now + tick_length;
+                break;
+            }
+            game_ref
+                .game_data
+                .update(&game_ref.data.nature_objects, &mut game_ref.data.generator);
+            next_tick += tick_length;
+            ticks += 1;
+        }
+
+        self.next_tick = Some(next_tick);
     }
 }
 
@@ -96,119 +134,48 @@ impl GameState for Game {
             if self.map_generation_state.is_none() {
                 let method_logger = self.logger.new(o!("Method" => "Game::background_update"));
 
-                game_ref.data.generator.reseed_with_default();
+                match self.parameters.seed {
+                    Some(seed) => game_ref.data.generator.reseed(seed),
+                    None => game_ref.data.generator.reseed_with_default(),
+                }
                 info!(
                     method_logger,
                     "Starting new game with seed: {}",
                     game_ref.data.generator.seed()
                 );
-                game_ref.game_data.reset();
-                self.map_generation_state = game_ref.game_data.generate_map(
-                    &mut game_ref.data.generator,
-                    &game_ref.data.settings,
-                    None,
-                );
+                game_ref.game_data.reset(self.parameters.map_size);
+                game_ref.game_data.seed = game_ref.data.generator.seed();
+                game_ref.game_data.preset = self.parameters.preset;
+                let preset = game_ref
+                    .data
+                    .map_presets
+                    .get(self.parameters.preset)
+                    .ok_or_else(|| format!("Unknown map preset: {}", self.parameters.preset))?;
+                info!(method_logger, "Generating map with preset: {}", preset.name);
+                self.map_generation_state = Some(MapGenerationState::new(
+                    MapGenerationPipeline::from_preset(preset, game_ref.data),
+                ));
                 return Ok(None);
             }
             let state = self.map_generation_state.as_mut().unwrap();
             if state.is_done() {
                 self.first_run = false;
                 self.map_generation_state = None;
+                self.camera.center_on(game_ref.game_data.camp_center);
+                game_ref.game_data.begin();
 
                 Ok(Some("DoneLoading".to_string()))
             } else {
                 game_ref.game_data.generate_map(
                     &mut game_ref.data.generator,
                     &game_ref.data.settings,
-                    Some(state),
+                    state,
                 );
 
                 Ok(None)
             }
         /*
-            game->SetSeason(EarlySpring);
-
-            std::priority_queue<std::pair<int, Coordinate> > spawnCenterCandidates;
-
-            for (int tries = 0; tries < 20; ++tries) {
-                std::pair<int,Coordinate> candidate(0, Random::ChooseInExtent(zero+100, Map::Inst()->Extent() - 100));
-
-                int riverDistance = 1000, hillDistance = 1000;
-                for (int i = 0; i < 4; ++i) {
-                    Direction dirs[4] = { WEST, EAST, NORTH, SOUTH };
-                    int distance = 200;
-                    Coordinate p = candidate.second + Coordinate::DirectionToCoordinate(dirs[i]) * distance;
-                    TCODLine::init(p.X(), p.Y(), candidate.second.X(), candidate.second.Y());
-                    do {
-                        if (Map::Inst()->IsInside(p)) {
-                            if (Map::Inst()->GetType(p) == TILEDITCH || Map::Inst()->GetType(p) == TILERIVERBED) {
-                                if (distance < riverDistance) riverDistance = distance;
-                                if (distance < 25) riverDistance = 2000;
-                            } else if (Map::Inst()->GetType(p) == TILEROCK) {
-                                if (distance < hillDistance) hillDistance = distance;
-                            }
-                        }
-                        --distance;
-                    } while (!TCODLine::step(p.Xptr(), p.Yptr()));
-                }
-
-                candidate.first = -hillDistance - riverDistance;
-                if (Map::Inst()->GetType(candidate.second) != TILEGRASS) candidate.first -= 10000;
-                spawnCenterCandidates.push(candidate);
-            }
-
-            Coordinate spawnTopCorner = spawnCenterCandidates.top().second - 20;
-            Coordinate spawnBottomCorner = spawnCenterCandidates.top().second + 20;
-
-            //Clear starting area
-            for (int x = spawnTopCorner.X(); x < spawnBottomCorner.X(); ++x) {
-                for (int y = spawnTopCorner.Y(); y < spawnBottomCorner.Y(); ++y) {
-                    Coordinate p(x,y);
-                    if (Map::Inst()->GetNatureObject(p) >= 0 && Random::Generate(2) < 2) {
-                        game->RemoveNatureObject(game->natureList[Map::Inst()->GetNatureObject(p)]);
-                    }
-                }
-            }
-
-            //we use Top+15, Bottom-15 to restrict the spawning zone of goblin&orc to the very center, instead of spilled over the whole camp
-            game->CreateNPCs(15, NPC::StringToNPCType("goblin"), spawnTopCorner+15, spawnBottomCorner-15);
-            game->CreateNPCs(6, NPC::StringToNPCType("orc"), spawnTopCorner+15, spawnBottomCorner-15);
-
-            game->CreateItems(30, Item::StringToItemType("Bloodberry seed"), spawnTopCorner, spawnBottomCorner);
-            game->CreateItems(5, Item::StringToItemType("Blueleaf seed"), spawnTopCorner, spawnBottomCorner);
-            game->CreateItems(30, Item::StringToItemType("Nightbloom seed"), spawnTopCorner, spawnBottomCorner);
-            game->CreateItems(20, Item::StringToItemType("Bread"), spawnTopCorner, spawnBottomCorner);
-
-            //we place two corpses on the map
-            Coordinate corpseLoc[2];
-
-            //find suitable location
-            for (int c = 0; c < 2; ++c) {
-                Coordinate p;
-                do {
-                    p = Random::ChooseInRectangle(spawnTopCorner, spawnBottomCorner);
-                } while(!Map::Inst()->IsWalkable(p));
-                corpseLoc[c] = p;
-            }
-
-            //initialize corpses
-            for (int c = 0; c < 2; ++c) {
-                game->CreateItem(corpseLoc[c], Item::StringToItemType("stone axe"));
-                game->CreateItem(corpseLoc[c], Item::StringToItemType("shovel"));
-                int corpseuid = game->CreateItem(corpseLoc[c], Item::StringToItemType("corpse"));
-                boost::shared_ptr<Item> corpse = game->itemList[corpseuid];
-                corpse->Name("Corpse(Human woodsman)");
-                corpse->Color(TCODColor::white);
-                for (int i = 0; i < 6; ++i)
-                    game->CreateBlood(Random::ChooseInRadius(corpseLoc[c], 2));
-            }
-
-            Camp::Inst()->SetCenter(spawnCenterCandidates.top().second);
-            game->CenterOn(spawnCenterCandidates.top().second);
-
-            Map::Inst()->SetTerritoryRectangle(spawnTopCorner, spawnBottomCorner, true);
-
-            Map::Inst()->weather->ApplySeasonalEffects();
+            // The start site is picked and populated by the start_site map generation step
 
             for (int i = 0; i < 10; ++i)
                 Game::Inst()->events->SpawnBenignFauna();
@@ -261,6 +228,7 @@ impl GameState for Game {
                 Script::Event::GameEnd();
             */
 
+            self.tick(game_ref);
             self.camera.update(game_ref);
 
             Ok(GameStateChange::None)
@@ -282,6 +250,19 @@ impl GameState for Game {
 
         game_ref.game_data.render_map(render_data);
 
+        game_ref.root.set_alignment(TextAlignment::Left);
+        game_ref.root.set_default_foreground(colors::GREY);
+        game_ref
+            .root
+            .print(0, 0, format!("Seed: {}", game_ref.game_data.seed));
+        game_ref.root.print_ex(
+            size_x - 1,
+            0,
+            BackgroundFlag::None,
+            TextAlignment::Right,
+            game_ref.game_data.calendar.to_string(),
+        );
+
         // TODO:
         //       if (drawUI) {
         //           UI::Inst()->Draw(console);
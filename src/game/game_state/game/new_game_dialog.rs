@@ -0,0 +1,282 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::base::{Position, Size};
+use crate::game::game_data::{self, NewGameParameters};
+use crate::game::game_state::game::Game;
+use crate::game::game_state::{GameState, GameStateChange, GameStateResult, GameStateUpdateResult};
+use crate::game::GameRef;
+use crate::ui::MessageBox;
+use std::borrow::Cow;
+use tcod::input::KeyCode;
+use tcod::{colors, BackgroundFlag, Console, TextAlignment};
+
+/// Lets the player choose the parameters of a new game before the map gets generated.
+pub struct NewGameDialog {
+    logger: slog::Logger,
+    fields: [NewGameField; 3],
+    focused_field: usize,
+    preset: usize,
+    message_box: bool,
+}
+
+impl NewGameDialog {
+    const WIDTH: i32 = 40;
+    const HEIGHT: i32 = 16;
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn game_state_change(parent_logger: slog::Logger) -> GameStateChange {
+        GameStateChange::PopPush(Self::game_state(parent_logger))
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn game_state(parent_logger: slog::Logger) -> Box<dyn GameState> {
+        Box::new(Self {
+            logger: parent_logger,
+            fields: [
+                NewGameField {
+                    label: "Seed (leave empty for random)",
+                    value: String::default(),
+                    invalid: false,
+                },
+                NewGameField {
+                    label: "Map width",
+                    value: String::default(),
+                    invalid: false,
+                },
+                NewGameField {
+                    label: "Map height",
+                    value: String::default(),
+                    invalid: false,
+                },
+            ],
+            focused_field: 0,
+            preset: 0,
+            message_box: false,
+        })
+    }
+
+    fn is_valid(field: usize, value: &str) -> bool {
+        if field == 0 {
+            value.is_empty() || value.parse::<u64>().is_ok()
+        } else {
+            NewGameParameters::parse_map_side(value).is_ok()
+        }
+    }
+
+    fn parameters(&self) -> game_data::Result<NewGameParameters> {
+        NewGameParameters::parse(
+            &self.fields[0].value,
+            &self.fields[1].value,
+            &self.fields[2].value,
+            self.preset,
+        )
+    }
+
+    fn draw_fields(&self, game_ref: &mut GameRef, current_y: &mut i32, x: i32) {
+        for (i, field) in self.fields.iter().enumerate() {
+            if self.focused_field == i {
+                game_ref.root.set_default_foreground(colors::GREEN);
+            }
+            game_ref.root.print(x + 1, *current_y, field.label);
+
+            if field.invalid {
+                game_ref.root.set_default_background(colors::DARKER_RED);
+            } else {
+                game_ref.root.set_default_background(colors::DARK_GREY);
+            }
+            game_ref.root.set_default_foreground(colors::WHITE);
+            game_ref.root.rect(
+                x + 3,
+                *current_y + 1,
+                Self::WIDTH - 7,
+                1,
+                true,
+                BackgroundFlag::Default,
+            );
+            game_ref.root.print(x + 3, *current_y + 1, &field.value);
+            if self.focused_field == i {
+                game_ref.root.put_char(
+                    x + 3 + field.value.len() as i32,
+                    *current_y + 1,
+                    '_',
+                    BackgroundFlag::Default,
+                );
+            }
+            game_ref.root.set_default_background(colors::BLACK);
+
+            *current_y += 3;
+        }
+    }
+
+    fn draw_preset(&self, game_ref: &mut GameRef, current_y: &mut i32, x: i32) {
+        game_ref.root.set_default_foreground(colors::WHITE);
+        game_ref
+            .root
+            .print(x + 1, *current_y, "Map preset (LEFT/RIGHT to change)");
+
+        if let Some(preset) = game_ref.data.map_presets.get(self.preset) {
+            game_ref.root.set_default_foreground(colors::GREEN);
+            game_ref
+                .root
+                .print(x + 3, *current_y + 1, format!("< {} >", preset.name));
+        }
+        game_ref.root.set_default_foreground(colors::WHITE);
+
+        *current_y += 3;
+    }
+
+    fn cycle_preset(&mut self, game_ref: &GameRef, forward: bool) {
+        let preset_count = game_ref.data.map_presets.all().len();
+        self.preset = if forward {
+            (self.preset + 1) % preset_count
+        } else {
+            (self.preset + preset_count - 1) % preset_count
+        };
+    }
+}
+
+impl GameState for NewGameDialog {
+    fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed("New game dialog")
+    }
+
+    fn activate(&mut self, game_ref: &mut GameRef) -> GameStateResult {
+        if self.message_box {
+            self.message_box = false;
+        } else {
+            if let Some(seed) = game_ref.config.seed() {
+                self.fields[0].value = seed.to_string();
+            }
+            let parameters = NewGameParameters::new(&game_ref.data.settings);
+            self.fields[1].value = parameters.map_size.width.to_string();
+            self.fields[2].value = parameters.map_size.height.to_string();
+            for (i, field) in self.fields.iter_mut().enumerate() {
+                field.invalid = !Self::is_valid(i, &field.value);
+            }
+            if let Some(preset) = game_ref
+                .config
+                .map_preset()
+                .and_then(|name| game_ref.data.map_presets.index_of(name))
+            {
+                self.preset = preset;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, game_ref: &mut GameRef) -> GameStateUpdateResult {
+        if game_ref.input.release_key_event.raw.code == KeyCode::Escape {
+            return Ok(GameStateChange::Pop);
+        } else if game_ref.input.release_key_event.raw.code == KeyCode::Enter {
+            return Ok(match self.parameters() {
+                Ok(parameters) => {
+                    GameStateChange::PopPush(Game::game_state(self.logger.clone(), parameters))
+                }
+                Err(e) => {
+                    self.message_box = true;
+                    GameStateChange::Push(MessageBox::game_state(
+                        game_ref,
+                        e.to_string(),
+                        "Understood".to_string(),
+                        Box::new(|| GameStateChange::Pop),
+                        None,
+                        None,
+                    ))
+                }
+            });
+        } else if game_ref.input.release_key_event.raw.code == KeyCode::Left {
+            self.cycle_preset(game_ref, false);
+        } else if game_ref.input.release_key_event.raw.code == KeyCode::Right {
+            self.cycle_preset(game_ref, true);
+        } else {
+            let field_value = &mut self.fields[self.focused_field].value;
+            let field_invalid = &mut self.fields[self.focused_field].invalid;
+
+            let key = game_ref.input.release_key_event.raw.printable;
+            let code = game_ref.input.release_key_event.raw.code;
+            let mut field_updated = false;
+            if key >= '0' && key <= '9' && field_value.len() < (Self::WIDTH - 7) as usize {
+                field_value.push(key);
+                field_updated = true;
+            } else if code == KeyCode::Backspace {
+                field_value.pop();
+                field_updated = true;
+            }
+            if field_updated {
+                *field_invalid = !Self::is_valid(self.focused_field, field_value);
+            }
+        }
+
+        let dialog_position = Position::new(
+            game_ref.root.width() / 2 - (Self::WIDTH / 2),
+            game_ref.root.height() / 2 - (Self::HEIGHT / 2),
+        );
+
+        let mouse_event = game_ref.input.mouse_event;
+        if mouse_event.clicked
+            && (dialog_position + Size::new(Self::WIDTH, Self::HEIGHT))
+                .contains_position(mouse_event.character_position)
+        {
+            let internal_position = mouse_event.character_position - dialog_position;
+            let field = (internal_position.y - 3) / 3;
+            if internal_position.y >= 3 && (field as usize) < self.fields.len() {
+                self.focused_field = field as usize;
+            }
+        }
+
+        Ok(GameStateChange::None)
+    }
+
+    fn draw(&mut self, game_ref: &mut GameRef) -> GameStateResult {
+        game_ref.root.set_alignment(TextAlignment::Left);
+
+        let x = game_ref.root.width() / 2 - (Self::WIDTH / 2);
+        let y = game_ref.root.height() / 2 - (Self::HEIGHT / 2);
+
+        game_ref.root.set_default_foreground(colors::WHITE);
+        game_ref.root.set_default_background(colors::BLACK);
+
+        game_ref.root.print_frame(
+            x,
+            y,
+            Self::WIDTH,
+            Self::HEIGHT,
+            true,
+            BackgroundFlag::Set,
+            Some("New game"),
+        );
+        game_ref
+            .root
+            .print(x + 1, y + 1, "ENTER to start, ESC to cancel.");
+
+        let mut current_y = y + 3;
+        self.draw_fields(game_ref, &mut current_y, x);
+        self.draw_preset(game_ref, &mut current_y, x);
+
+        Ok(())
+    }
+}
+
+struct NewGameField {
+    label: &'static str,
+    value: String,
+    invalid: bool,
+}
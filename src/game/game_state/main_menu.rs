@@ -22,15 +22,18 @@ pub mod keys_dialog;
 pub mod settings_dialog;
 //pub mod tile_sets_dialog;
 
-use crate::game::game_state::game::ConfirmNewGame;
+use crate::data::paths::PathProvider;
+use crate::game::game_data::{NewGameParameters, SaveState};
+use crate::game::game_state::game::{self, ConfirmNewGame};
 use crate::game::game_state::main_menu::keys_dialog::KeysDialog;
 use crate::game::game_state::main_menu::settings_dialog::SettingsDialog;
 use crate::game::game_state::GameStateUpdateResult;
 use crate::game::game_state::{GameState, GameStateChange, GameStateResult};
 use crate::game::{Game, GameRef};
 use derivative::Derivative;
-use slog::{debug, o};
+use slog::{debug, error, info, o};
 use std::borrow::Cow;
+use std::path::PathBuf;
 use tcod::colors;
 use tcod::console::BackgroundFlag::{Default as BackgroundDefault, Set};
 use tcod::console::{Console, Root};
@@ -60,13 +63,13 @@ impl MainMenu {
             label: "Load",
             shortcut: 'l',
             active: ActiveState::HasSaves,
-            new_state: MainMenu::quit_game_state_change,
+            new_state: MainMenu::load_game_state_change,
         },
         MainMenuEntry {
             label: "Save",
             shortcut: 's',
             active: ActiveState::IfRunning,
-            new_state: MainMenu::quit_game_state_change,
+            new_state: MainMenu::save_game_state_change,
         },
         MainMenuEntry {
             label: "Settings",
@@ -109,10 +112,51 @@ impl MainMenu {
         })
     }
 
+    /// The save file the Save entry writes to, until saves can be given a name.
+    const SAVE_FILE_NAME: &'static str = "quicksave.json";
+
     fn quit_game_state_change(_: &mut GameRef) -> GameStateChange {
         GameStateChange::EndGame
     }
 
+    fn save_file_path(game_ref: &GameRef) -> PathBuf {
+        game_ref
+            .data
+            .paths
+            .saves_directory()
+            .join(Self::SAVE_FILE_NAME)
+    }
+
+    /// Starts a game from the save file, generating its map again from the seed it was saved with.
+    fn load_game_state_change(game_ref: &mut GameRef) -> GameStateChange {
+        let save_file_path = Self::save_file_path(game_ref);
+        match SaveState::load(&save_file_path) {
+            Ok(save_state) => {
+                info!(game_ref.logger, "Loading game from {:?}", save_file_path);
+                let parameters = NewGameParameters {
+                    seed: Some(save_state.seed),
+                    map_size: save_state.map_size,
+                    preset: save_state.preset,
+                };
+                GameStateChange::Push(game::Game::game_state(game_ref.logger.clone(), parameters))
+            }
+            Err(e) => {
+                error!(game_ref.logger, "Could not load game: {}", e);
+                GameStateChange::None
+            }
+        }
+    }
+
+    fn save_game_state_change(game_ref: &mut GameRef) -> GameStateChange {
+        let save_file_path = Self::save_file_path(game_ref);
+        match game_ref.game_data.save(&save_file_path) {
+            Ok(()) => info!(game_ref.logger, "Saved game to {:?}", save_file_path),
+            Err(e) => error!(game_ref.logger, "Could not save game: {}", e),
+        }
+
+        GameStateChange::None
+    }
+
     fn render(&mut self, game_ref: &mut GameRef, background: bool) -> GameStateResult {
         let render_data = self.render_data.as_mut().unwrap();
 
@@ -347,7 +391,11 @@ impl MainMenuEntry {
         match self.active {
             ActiveState::Always => true,
             ActiveState::IfRunning => game_ref.game_data.running,
-            _ => false,
+            // Loading while a game is running would leave both games on the stack
+            ActiveState::HasSaves => {
+                !game_ref.game_data.running && MainMenu::save_file_path(game_ref).is_file()
+            }
+            ActiveState::Never => false,
         }
     }
 }
@@ -0,0 +1,136 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Generates a map without opening a window, and writes the result to disk so that changes to
+//! the map generator can be judged without starting the game.
+
+use crate::data::Data;
+use crate::game::game_data::{GameData, MapGenerationPipeline, NewGameParameters};
+use crate::Config;
+use slog::{info, o};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Cannot export map to {:?}, only .ppm images are supported", path))]
+    UnsupportedFormat { path: PathBuf },
+    #[snafu(display("Cannot write to {:?} because: {}", path, source))]
+    MapWrite {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[snafu(display("Cannot write map summary: {}", source))]
+    JsonSerialization { source: serde_json::Error },
+    #[snafu(display("There is no map preset named '{}'", name))]
+    UnknownPreset { name: String },
+}
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// Runs every map generation stage and writes the tile map to `image_path`, the height map next to
+/// it with a `_height` suffix, and a JSON summary of the map with a `.json` extension.
+pub fn generate_and_export(
+    parent_logger: &slog::Logger,
+    config: &Config,
+    data: &mut Data,
+    image_path: &Path,
+) -> Result {
+    let logger = parent_logger.new(o!());
+
+    ensure!(
+        image_path
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("ppm")),
+        UnsupportedFormat {
+            path: image_path.to_path_buf(),
+        }
+    );
+
+    let map_presets = &data.map_presets;
+    let preset = match config.map_preset() {
+        Some(name) => map_presets
+            .index_of(name)
+            .and_then(|i| map_presets.get(i))
+            .context(UnknownPreset { name })?,
+        None => &map_presets.all()[0],
+    };
+    let pipeline = MapGenerationPipeline::from_preset(preset, data);
+
+    match config.seed() {
+        Some(seed) => data.generator.reseed(seed),
+        None => data.generator.reseed_with_default(),
+    }
+    info!(
+        logger,
+        "Generating map with seed: {} and preset: {}",
+        data.generator.seed(),
+        preset.name
+    );
+
+    let mut game_data = GameData::new();
+    game_data.reset(NewGameParameters::new(&data.settings).map_size);
+    game_data.seed = data.generator.seed();
+    let state = game_data.generate_complete_map(pipeline, &mut data.generator, &data.settings);
+
+    let height_path = image_path.with_file_name(format!(
+        "{}_height.ppm",
+        image_path
+            .file_stem()
+            .map_or_else(Default::default, |stem| stem.to_string_lossy())
+    ));
+    let summary_path = image_path.with_extension("json");
+
+    write_image(image_path, |writer| game_data.map.write_tile_ppm(writer))?;
+    write_image(&height_path, |writer| {
+        game_data.map.write_height_ppm(writer)
+    })?;
+
+    let summary = game_data.map_summary(&state);
+    let summary_string = serde_json::to_string_pretty(&summary).context(JsonSerialization)?;
+    fs::write(&summary_path, summary_string).with_context(|| MapWrite {
+        path: summary_path.clone(),
+    })?;
+
+    info!(
+        logger,
+        "Exported map to {:?}, {:?} and {:?}", image_path, height_path, summary_path
+    );
+
+    Ok(())
+}
+
+fn write_image<F>(path: &Path, write: F) -> Result
+where
+    F: FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+{
+    let file = File::create(path).with_context(|| MapWrite {
+        path: path.to_path_buf(),
+    })?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)
+        .and_then(|_| writer.flush())
+        .with_context(|| MapWrite {
+            path: path.to_path_buf(),
+        })?;
+
+    Ok(())
+}
@@ -76,6 +76,7 @@ pub mod ui;
 pub mod util;
 
 use clap::ArgMatches;
+use std::path::{Path, PathBuf};
 
 // TODO: Most of these won't make sense in this version. Consider removing most of them.
 #[derive(Clone, Debug)]
@@ -84,6 +85,9 @@ pub struct Config {
     dev_mode: bool,
     no_dumps: bool,
     verbosity: u64,
+    seed: Option<u64>,
+    map_preset: Option<String>,
+    map_export_path: Option<PathBuf>,
 }
 
 impl Config {
@@ -93,9 +97,38 @@ impl Config {
             dev_mode: arg_matches.is_present("dev_mode"),
             no_dumps: arg_matches.is_present("no_dumps"),
             verbosity: arg_matches.occurrences_of("verbose"),
+            seed: arg_matches
+                .value_of("seed")
+                .map(str::parse)
+                .transpose()
+                .map_err(|e| format!("Invalid seed: {}", e))?,
+            map_preset: arg_matches.value_of("preset").map(str::to_string),
+            map_export_path: if arg_matches.is_present("generate_map") {
+                Some(PathBuf::from(
+                    arg_matches.value_of("out").unwrap_or("map.ppm"),
+                ))
+            } else {
+                None
+            },
         })
     }
 
+    /// The map generation seed given on the command line, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// The name of the map preset given on the command line, if any.
+    pub fn map_preset(&self) -> Option<&str> {
+        self.map_preset.as_ref().map(String::as_str)
+    }
+
+    /// Where to export a headlessly generated map to, if the game was asked to do so instead of
+    /// starting normally.
+    pub fn map_export_path(&self) -> Option<&Path> {
+        self.map_export_path.as_ref().map(PathBuf::as_path)
+    }
+
     pub fn logging_level(&self) -> slog::Level {
         match self.verbosity {
             0 => slog::Level::Info,
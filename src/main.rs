@@ -20,7 +20,7 @@
 
 use clap::{load_yaml, App};
 use goblin_camp_revival::data::Data;
-use goblin_camp_revival::game::Game;
+use goblin_camp_revival::game::{map_export, Game};
 use goblin_camp_revival::Config;
 use slog::{info, o, Drain};
 use snafu::{ResultExt, Snafu};
@@ -45,7 +45,15 @@ fn run() -> Result<(), InitializationError> {
     info!(root_logger, "Starting {} {}", Game::NAME, Game::VERSION);
 
     // Create all "singleton" types
-    let data = Data::new(&root_logger).context(DataInitialization)?;
+    let mut data = Data::new(&root_logger).context(DataInitialization)?;
+
+    if let Some(map_export_path) = config.map_export_path() {
+        map_export::generate_and_export(&root_logger, &config, &mut data, map_export_path)
+            .context(MapExport)?;
+
+        info!(root_logger, "Ending {} {}", Game::NAME, Game::VERSION);
+        return Ok(());
+    }
 
     // - Show loading screen while doing heavy I/O?
 
@@ -65,6 +73,7 @@ fn main() {
             InitializationError::ArgumentParsing { source } => source,
             InitializationError::DataInitialization { source } => Box::from(source),
             InitializationError::GameRun { source } => Box::from(source),
+            InitializationError::MapExport { source } => Box::from(source),
         };
         eprintln!("Error occurred while {}: {}", cause, source);
         process::exit(exit_code);
@@ -86,6 +95,10 @@ pub enum InitializationError {
     GameRun {
         source: goblin_camp_revival::game::Error,
     },
+    #[snafu(display("exporting the generated map"))]
+    MapExport {
+        source: goblin_camp_revival::game::map_export::Error,
+    },
 }
 
 impl From<&InitializationError> for i32 {
@@ -94,6 +107,7 @@ impl From<&InitializationError> for i32 {
             InitializationError::ArgumentParsing { .. } => 1,
             InitializationError::DataInitialization { .. } => 2,
             InitializationError::GameRun { .. } => 3,
+            InitializationError::MapExport { .. } => 4,
         }
     }
 }
@@ -19,10 +19,12 @@
 
 mod array2d;
 mod safe_console;
+mod slot_list;
 pub mod tcod;
 
 pub use array2d::*;
 pub use safe_console::*;
+pub use slot_list::*;
 use std::mem;
 
 pub trait Flip {
@@ -0,0 +1,276 @@
+/*
+    Copyright 2019 Alexander Krivács Schrøder
+
+    This file is part of Goblin Camp Revival.
+
+    Goblin Camp Revival is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Goblin Camp Revival is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Goblin Camp Revival.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// A handle to a value stored in a [`SlotList`]. Handles stay valid until their value is removed,
+/// after which they no longer refer to anything, even if their slot gets reused.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SlotHandle<T> {
+    index: u32,
+    generation: u32,
+    #[serde(skip)]
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SlotHandle<T> {
+    /// The slot the handle points to, which is unique among the values currently in the list.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// These are implemented by hand, since deriving them would require `T` to implement them as well.
+impl<T> Clone for SlotHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SlotHandle<T> {}
+
+impl<T> PartialEq for SlotHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for SlotHandle<T> {}
+
+impl<T> Hash for SlotHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for SlotHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SlotHandle({}v{})", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// A list of values that are referred to by [`SlotHandle`]s instead of by shared pointers. Values
+/// never move once inserted, and slots freed by removing values are reused by later insertions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotList<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> SlotList<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> SlotHandle<T> {
+        self.len += 1;
+        let index = if let Some(index) = self.free.pop() {
+            self.slots[index as usize].value = Some(value);
+            index
+        } else {
+            self.slots.push(Slot {
+                generation: 0,
+                value: Some(value),
+            });
+            (self.slots.len() - 1) as u32
+        };
+
+        SlotHandle {
+            index,
+            generation: self.slots[index as usize].generation,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Removes the value `handle` refers to, returning it, unless it has already been removed.
+    pub fn remove(&mut self, handle: SlotHandle<T>) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index())?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+
+        Some(value)
+    }
+
+    pub fn get(&self, handle: SlotHandle<T>) -> Option<&T> {
+        self.slots
+            .get(handle.index())
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: SlotHandle<T>) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    pub fn contains(&self, handle: SlotHandle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Goes through the values in the order of their slots, which is the order they were inserted
+    /// in as long as nothing has been removed.
+    pub fn iter(&self) -> impl Iterator<Item = (SlotHandle<T>, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value.as_ref().map(|value| {
+                (
+                    SlotHandle {
+                        index: index as u32,
+                        generation: slot.generation,
+                        _marker: PhantomData,
+                    },
+                    value,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (SlotHandle<T>, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+                slot.value.as_mut().map(|value| {
+                    (
+                        SlotHandle {
+                            index: index as u32,
+                            generation,
+                            _marker: PhantomData,
+                        },
+                        value,
+                    )
+                })
+            })
+    }
+
+    /// Removes every value. The slots are kept for reuse, so handles to the removed values don't
+    /// come to refer to values inserted afterwards.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        // Freed in reverse, so the slots are reused from the front like in a new list
+        for (index, slot) in self.slots.iter_mut().enumerate().rev() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+            self.free.push(index as u32);
+        }
+        self.len = 0;
+    }
+}
+
+impl<T> Default for SlotList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_handles_stay_invalid_when_slot_is_reused() {
+        let mut list = SlotList::new();
+        let first = list.insert(1);
+        let second = list.insert(2);
+        assert_eq!(list.remove(first), Some(1));
+        assert_eq!(list.remove(first), None);
+
+        let third = list.insert(3);
+        assert_eq!(third.index(), first.index());
+        assert_eq!(list.get(first), None);
+        assert_eq!(list.get(third), Some(&3));
+        assert_eq!(list.get(second), Some(&2));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.iter().map(|(_, &v)| v).collect::<Vec<_>>(), vec![3, 2]);
+    }
+
+    #[test]
+    fn handles_from_before_a_clear_stay_invalid() {
+        let mut list = SlotList::new();
+        let first = list.insert(1);
+        let second = list.insert(2);
+        list.remove(second);
+
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list.get(first), None);
+
+        let third = list.insert(3);
+        let fourth = list.insert(4);
+        assert_eq!(third.index(), first.index());
+        assert_eq!(list.get(first), None);
+        assert_eq!(list.get(second), None);
+        assert_eq!(list.remove(first), None);
+        assert_eq!(list.get(third), Some(&3));
+        assert_eq!(list.get(fourth), Some(&4));
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn handles_stay_valid_or_invalid_across_saving_and_loading() {
+        let mut list = SlotList::new();
+        let first = list.insert(1);
+        let second = list.insert(2);
+        list.remove(first);
+
+        let saved = serde_json::to_string(&(&list, first, second)).unwrap();
+        let (mut list, first, second): (SlotList<i32>, SlotHandle<i32>, SlotHandle<i32>) =
+            serde_json::from_str(&saved).unwrap();
+        assert_eq!(list.get(first), None);
+        assert_eq!(list.get(second), Some(&2));
+
+        let third = list.insert(3);
+        assert_eq!(third.index(), first.index());
+        assert_eq!(list.get(first), None);
+        assert_eq!(list.len(), 2);
+    }
+}